@@ -0,0 +1,228 @@
+//! Runtime loading of `libaaudio`, enabled with the `dynamic` feature.
+//!
+//! When this feature is enabled, the library is not linked at load time. Instead, it is opened
+//! with `dlopen()` on the first call to any `AAudio*` function of this crate, and every
+//! function is resolved separately with `dlsym()`. This allows an application to run on
+//! devices where AAudio or some of its newer functions are missing. Use [`is_available`]
+//! to check whether a particular function can be used.
+//!
+//! Calling a function that could not be resolved does nothing. Functions that return
+//! a result code return `ERROR_UNIMPLEMENTED` instead, and getters return the value AAudio
+//! would report for an unspecified parameter.
+//!
+//! [`load_library`] can be used to load a different library, for example a stub that
+//! implements the AAudio API on a host system.
+//!
+//! [`is_available`]: crate::is_available
+
+use std::error;
+use std::ffi::CStr;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// The name of the library opened when [`load_library`] was not called.
+pub const DEFAULT_LIBRARY: &str = "libaaudio.so";
+
+/// Error returned from [`load_library`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LoadError {
+    /// A library was already loaded, either explicitly or by calling an AAudio function.
+    AlreadyLoaded,
+
+    /// `dlopen()` failed with the given message.
+    Open(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::AlreadyLoaded => f.write_str("An AAudio library is already loaded."),
+            Self::Open(message) => write!(f, "Failed to load the AAudio library: {}", message),
+        }
+    }
+}
+
+impl error::Error for LoadError {}
+
+struct Library {
+    functions: api::Functions,
+}
+
+static LIBRARY: OnceLock<Result<Library, LoadError>> = OnceLock::new();
+
+fn open(path: &CStr) -> Result<Library, LoadError> {
+    unsafe {
+        let handle = libc::dlopen(path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+        if handle.is_null() {
+            let message = libc::dlerror();
+            let message = if message.is_null() {
+                path.to_string_lossy().into_owned()
+            } else {
+                CStr::from_ptr(message).to_string_lossy().into_owned()
+            };
+            return Err(LoadError::Open(message));
+        }
+        // The library is never closed: resolved functions are used for the lifetime of the process.
+        Ok(Library {
+            functions: api::Functions::load(handle),
+        })
+    }
+}
+
+fn library() -> Option<&'static Library> {
+    LIBRARY
+        .get_or_init(|| {
+            let path = format!("{}\0", DEFAULT_LIBRARY);
+            open(CStr::from_bytes_with_nul(path.as_bytes()).unwrap())
+        })
+        .as_ref()
+        .ok()
+}
+
+/// Loads the AAudio implementation from `path` instead of [`DEFAULT_LIBRARY`].
+///
+/// This must be called before any AAudio function is used.
+pub fn load_library(path: &CStr) -> Result<(), LoadError> {
+    let mut result = Err(LoadError::AlreadyLoaded);
+    LIBRARY.get_or_init(|| {
+        let library = open(path);
+        result = library.as_ref().map(|_| ()).map_err(|e| e.clone());
+        library
+    });
+    result
+}
+
+/// Returns the error that occurred while loading the library, if any.
+///
+/// Loads the library if it was not loaded yet.
+pub fn load_error() -> Option<LoadError> {
+    library();
+    LIBRARY.get().and_then(|l| l.as_ref().err()).cloned()
+}
+
+pub(crate) fn is_available(name: &str) -> bool {
+    library().is_some_and(|l| l.functions.is_resolved(name))
+}
+
+macro_rules! functions {
+    ($(fn $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty = $fallback:expr)?;)*) => {
+        #[allow(non_snake_case)]
+        pub(super) struct Functions {
+            $($name: Option<unsafe extern "C" fn($($ty),*) $(-> $ret)?>,)*
+        }
+
+        impl Functions {
+            pub(super) unsafe fn load(handle: *mut c_void) -> Self {
+                Self {
+                    $($name: mem::transmute::<
+                        *mut c_void,
+                        Option<unsafe extern "C" fn($($ty),*) $(-> $ret)?>,
+                    >(libc::dlsym(
+                        handle,
+                        concat!(stringify!($name), "\0").as_ptr() as *const libc::c_char,
+                    )),)*
+                }
+            }
+
+            pub(super) fn is_resolved(&self, name: &str) -> bool {
+                match name {
+                    $(stringify!($name) => self.$name.is_some(),)*
+                    _ => false,
+                }
+            }
+        }
+
+        $(
+            pub unsafe extern "C" fn $name($($arg: $ty),*) $(-> $ret)? {
+                match super::library().and_then(|l| l.functions.$name) {
+                    Some(function) => function($($arg),*),
+                    None => { $($fallback)? }
+                }
+            }
+        )*
+    };
+}
+
+/// Trampolines with the same signatures as the functions declared in the crate root.
+/// See the declarations there for the documentation.
+pub(crate) mod api {
+    #![allow(non_snake_case, clippy::missing_safety_doc)]
+
+    use std::ffi::c_void;
+    use std::mem;
+
+    use crate::*;
+
+    functions! {
+        fn AAudio_convertResultToText(return_code: i32) -> *const libc::c_char = std::ptr::null();
+        fn AAudio_createStreamBuilder(builder: *mut *mut AAudioStreamBuilder) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStreamBuilder_setDeviceId(builder: *mut AAudioStreamBuilder, device_id: i32);
+        fn AAudioStreamBuilder_setSampleRate(builder: *mut AAudioStreamBuilder, sample_rate: i32);
+        fn AAudioStreamBuilder_setChannelCount(builder: *mut AAudioStreamBuilder, channel_count: i32);
+        fn AAudioStreamBuilder_setChannelMask(builder: *mut AAudioStreamBuilder, channel_mask: u32);
+        fn AAudioStreamBuilder_setFormat(builder: *mut AAudioStreamBuilder, format: i32);
+        fn AAudioStreamBuilder_setSharingMode(builder: *mut AAudioStreamBuilder, sharing_mode: i32);
+        fn AAudioStreamBuilder_setDirection(builder: *mut AAudioStreamBuilder, direction: i32);
+        fn AAudioStreamBuilder_setBufferCapacityInFrames(builder: *mut AAudioStreamBuilder, num_frames: i32);
+        fn AAudioStreamBuilder_setPerformanceMode(builder: *mut AAudioStreamBuilder, mode: i32);
+        fn AAudioStreamBuilder_setUsage(builder: *mut AAudioStreamBuilder, usage: i32);
+        fn AAudioStreamBuilder_setContentType(builder: *mut AAudioStreamBuilder, content_type: i32);
+        fn AAudioStreamBuilder_setInputPreset(builder: *mut AAudioStreamBuilder, input_preset: i32);
+        fn AAudioStreamBuilder_setAllowedCapturePolicy(builder: *mut AAudioStreamBuilder, capture_policy: i32);
+        fn AAudioStreamBuilder_setSessionId(builder: *mut AAudioStreamBuilder, session_id: i32);
+        fn AAudioStreamBuilder_setPrivacySensitive(builder: *mut AAudioStreamBuilder, privacy_sensitive: bool);
+        fn AAudioStreamBuilder_setSpatializationBehavior(builder: *mut AAudioStreamBuilder, spatialization_behavior: i32);
+        fn AAudioStreamBuilder_setIsContentSpatialized(builder: *mut AAudioStreamBuilder, is_spatialized: bool);
+        fn AAudioStreamBuilder_setPackageName(builder: *mut AAudioStreamBuilder, package_name: *const libc::c_char);
+        fn AAudioStreamBuilder_setAttributionTag(builder: *mut AAudioStreamBuilder, attribution_tag: *const libc::c_char);
+        fn AAudioStreamBuilder_setDataCallback(builder: *mut AAudioStreamBuilder, callback: DataCallback, user_data: *mut c_void);
+        fn AAudioStreamBuilder_setPartialDataCallback(builder: *mut AAudioStreamBuilder, callback: PartialDataCallback, user_data: *mut c_void);
+        fn AAudioStreamBuilder_setFramesPerDataCallback(builder: *mut AAudioStreamBuilder, num_frames: i32);
+        fn AAudioStreamBuilder_setErrorCallback(builder: *mut AAudioStreamBuilder, callback: ErrorCallback, user_data: *mut c_void);
+        fn AAudioStreamBuilder_setPresentationEndCallback(builder: *mut AAudioStreamBuilder, callback: PresentationEndCallback, user_data: *mut c_void);
+        fn AAudioStreamBuilder_openStream(builder: *mut AAudioStreamBuilder, stream: *mut *mut AAudioStream) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStreamBuilder_delete(builder: *mut AAudioStreamBuilder) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_release(stream: *mut AAudioStream) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_close(stream: *mut AAudioStream) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_requestStart(stream: *mut AAudioStream) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_requestPause(stream: *mut AAudioStream) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_requestFlush(stream: *mut AAudioStream) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_requestStop(stream: *mut AAudioStream) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_getState(stream: *mut AAudioStream) -> i32 = STREAM_STATE_UNKNOWN;
+        fn AAudioStream_waitForStateChange(stream: *mut AAudioStream, input_state: i32, next_state: *mut i32, timeout_nanoseconds: i64) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_read(stream: *mut AAudioStream, buffer: *mut c_void, num_frames: i32, timeout_nanoseconds: i64) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_write(stream: *mut AAudioStream, buffer: *const c_void, num_frames: i32, timeout_nanoseconds: i64) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_setBufferSizeInFrames(stream: *mut AAudioStream, num_frames: i32) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_setOffloadDelayPadding(stream: *mut AAudioStream, delay_in_frames: i32, padding_in_frames: i32) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_getOffloadDelay(stream: *mut AAudioStream) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_getOffloadPadding(stream: *mut AAudioStream) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_setOffloadEndOfStream(stream: *mut AAudioStream) -> i32 = ERROR_UNIMPLEMENTED;
+        fn AAudioStream_getBufferSizeInFrames(stream: *mut AAudioStream) -> i32 = UNSPECIFIED;
+        fn AAudioStream_getFramesPerBurst(stream: *mut AAudioStream) -> i32 = UNSPECIFIED;
+        fn AAudioStream_getBufferCapacityInFrames(stream: *mut AAudioStream) -> i32 = UNSPECIFIED;
+        fn AAudioStream_getFramesPerDataCallback(stream: *mut AAudioStream) -> i32 = UNSPECIFIED;
+        fn AAudioStream_getXRunCount(stream: *mut AAudioStream) -> i32 = 0;
+        fn AAudioStream_getSampleRate(stream: *mut AAudioStream) -> i32 = UNSPECIFIED;
+        fn AAudioStream_getChannelCount(stream: *mut AAudioStream) -> i32 = UNSPECIFIED;
+        fn AAudioStream_getChannelMask(stream: *mut AAudioStream) -> u32 = UNSPECIFIED as u32;
+        fn AAudioStream_getHardwareChannelCount(stream: *mut AAudioStream) -> i32 = UNSPECIFIED;
+        fn AAudioStream_getHardwareSampleRate(stream: *mut AAudioStream) -> i32 = UNSPECIFIED;
+        fn AAudioStream_getHardwareFormat(stream: *mut AAudioStream) -> i32 = FORMAT_INVALID;
+        fn AAudioStream_getDeviceId(stream: *mut AAudioStream) -> i32 = UNSPECIFIED;
+        fn AAudioStream_getFormat(stream: *mut AAudioStream) -> i32 = UNSPECIFIED;
+        fn AAudioStream_getSharingMode(stream: *mut AAudioStream) -> i32 = SHARING_SHARED;
+        fn AAudioStream_getPerformanceMode(stream: *mut AAudioStream) -> i32 = PERFORMANCE_MODE_NONE;
+        fn AAudioStream_getDirection(stream: *mut AAudioStream) -> i32 = DIRECTION_OUTPUT;
+        fn AAudioStream_getFramesWritten(stream: *mut AAudioStream) -> i64 = 0;
+        fn AAudioStream_getFramesRead(stream: *mut AAudioStream) -> i64 = 0;
+        fn AAudioStream_getSessionId(stream: *mut AAudioStream) -> i32 = SESSION_ID_NONE;
+        fn AAudioStream_getUsage(stream: *mut AAudioStream) -> i32 = USAGE_MEDIA;
+        fn AAudioStream_getContentType(stream: *mut AAudioStream) -> i32 = CONTENT_TYPE_MUSIC;
+        fn AAudioStream_getInputPreset(stream: *mut AAudioStream) -> i32 = INPUT_PRESET_VOICE_RECOGNITION;
+        fn AAudioStream_getAllowedCapturePolicy(stream: *mut AAudioStream) -> i32 = ALLOW_CAPTURE_BY_ALL;
+        fn AAudioStream_isPrivacySensitive(stream: *mut AAudioStream) -> bool = false;
+        fn AAudioStream_getSpatializationBehavior(stream: *mut AAudioStream) -> i32 = SPATIALIZATION_BEHAVIOR_AUTO;
+        fn AAudioStream_isContentSpatialized(stream: *mut AAudioStream) -> bool = false;
+        fn AAudioStream_getTimestamp(stream: *mut AAudioStream, clockid: libc::clockid_t, frame_position: *mut i64, time_nanoseconds: *mut i64) -> i32 = ERROR_UNIMPLEMENTED;
+    }
+}
@@ -1,8 +1,40 @@
+//! Low-level wrappers for AAudio API.
+
 extern crate libc;
 
 use std::ffi::c_void;
 
-/// Low-level wrappers for AAudio API.
+#[cfg(feature = "mock")]
+pub mod mock;
+
+#[cfg(feature = "mock")]
+pub use mock::api::*;
+
+#[cfg(all(feature = "dynamic", not(feature = "mock")))]
+pub mod dynamic;
+
+#[cfg(all(feature = "dynamic", not(feature = "mock")))]
+pub use dynamic::api::*;
+
+/// Returns `true` if the AAudio function with the given name, eg. `"AAudioStreamBuilder_setUsage"`,
+/// can be called on this device.
+///
+/// With the `dynamic` feature, this reports whether the function was found in the library.
+/// Otherwise every function is assumed to be available.
+#[cfg(all(feature = "dynamic", not(feature = "mock")))]
+pub fn is_available(function: &str) -> bool {
+    dynamic::is_available(function)
+}
+
+/// Returns `true` if the AAudio function with the given name, eg. `"AAudioStreamBuilder_setUsage"`,
+/// can be called on this device.
+///
+/// With the `dynamic` feature, this reports whether the function was found in the library.
+/// Otherwise every function is assumed to be available.
+#[cfg(not(all(feature = "dynamic", not(feature = "mock"))))]
+pub fn is_available(_function: &str) -> bool {
+    true
+}
 
 pub enum AAudioStream {}
 pub enum AAudioStreamBuilder {}
@@ -17,11 +49,21 @@ pub const SESSION_ID_NONE: i32 = -1;
 pub const SESSION_ID_ALLOCATE: i32 = 0;
 
 pub const DIRECTION_OUTPUT: i32 = 0;
-pub const DIRECTION_INPUT: i32 = 0;
+pub const DIRECTION_INPUT: i32 = 1;
 
 pub const FORMAT_INVALID: i32 = -1;
 pub const FORMAT_PCM_I16: i32 = 1;
 pub const FORMAT_PCM_FLOAT: i32 = 2;
+pub const FORMAT_PCM_I24_PACKED: i32 = 3;
+pub const FORMAT_PCM_I32: i32 = 4;
+pub const FORMAT_IEC61937: i32 = 5;
+pub const FORMAT_MP3: i32 = 6;
+pub const FORMAT_AAC_LC: i32 = 7;
+pub const FORMAT_AAC_HE_V1: i32 = 8;
+pub const FORMAT_AAC_HE_V2: i32 = 9;
+pub const FORMAT_AAC_ELD: i32 = 10;
+pub const FORMAT_AAC_XHE: i32 = 11;
+pub const FORMAT_OPUS: i32 = 12;
 
 pub const SHARING_EXCLUSIVE: i32 = 0;
 pub const SHARING_SHARED: i32 = 1;
@@ -59,12 +101,16 @@ pub const ALLOW_CAPTURE_BY_ALL: i32 = 1;
 pub const ALLOW_CAPTURE_BY_SYSTEM: i32 = 2;
 pub const ALLOW_CAPTURE_BY_NONE: i32 = 3;
 
+pub const SPATIALIZATION_BEHAVIOR_AUTO: i32 = 1;
+pub const SPATIALIZATION_BEHAVIOR_NEVER: i32 = 2;
+
 pub const CALLBACK_CONTINUE: i32 = 0;
 pub const CALLBACK_STOP: i32 = 1;
 
 pub const PERFORMANCE_MODE_NONE: i32 = 10;
 pub const PERFORMANCE_MODE_POWER_SAVING: i32 = 11;
 pub const PERFORMANCE_MODE_LOW_LATENCY: i32 = 12;
+pub const PERFORMANCE_MODE_POWER_SAVING_OFFLOADED: i32 = 13;
 
 pub const STREAM_STATE_UNINITIALIZED: i32 = 0;
 pub const STREAM_STATE_UNKNOWN: i32 = 1;
@@ -81,6 +127,89 @@ pub const STREAM_STATE_CLOSING: i32 = 11;
 pub const STREAM_STATE_CLOSED: i32 = 12;
 pub const STREAM_STATE_DISCONNECTED: i32 = 13;
 
+/// Invalid channel mask.
+pub const CHANNEL_INVALID: u32 = 0xFFFF_FFFF;
+
+pub const CHANNEL_FRONT_LEFT: u32 = 1 << 0;
+pub const CHANNEL_FRONT_RIGHT: u32 = 1 << 1;
+pub const CHANNEL_FRONT_CENTER: u32 = 1 << 2;
+pub const CHANNEL_LOW_FREQUENCY: u32 = 1 << 3;
+pub const CHANNEL_BACK_LEFT: u32 = 1 << 4;
+pub const CHANNEL_BACK_RIGHT: u32 = 1 << 5;
+pub const CHANNEL_FRONT_LEFT_OF_CENTER: u32 = 1 << 6;
+pub const CHANNEL_FRONT_RIGHT_OF_CENTER: u32 = 1 << 7;
+pub const CHANNEL_BACK_CENTER: u32 = 1 << 8;
+pub const CHANNEL_SIDE_LEFT: u32 = 1 << 9;
+pub const CHANNEL_SIDE_RIGHT: u32 = 1 << 10;
+pub const CHANNEL_TOP_CENTER: u32 = 1 << 11;
+pub const CHANNEL_TOP_FRONT_LEFT: u32 = 1 << 12;
+pub const CHANNEL_TOP_FRONT_CENTER: u32 = 1 << 13;
+pub const CHANNEL_TOP_FRONT_RIGHT: u32 = 1 << 14;
+pub const CHANNEL_TOP_BACK_LEFT: u32 = 1 << 15;
+pub const CHANNEL_TOP_BACK_CENTER: u32 = 1 << 16;
+pub const CHANNEL_TOP_BACK_RIGHT: u32 = 1 << 17;
+pub const CHANNEL_TOP_SIDE_LEFT: u32 = 1 << 18;
+pub const CHANNEL_TOP_SIDE_RIGHT: u32 = 1 << 19;
+pub const CHANNEL_BOTTOM_FRONT_LEFT: u32 = 1 << 20;
+pub const CHANNEL_BOTTOM_FRONT_CENTER: u32 = 1 << 21;
+pub const CHANNEL_BOTTOM_FRONT_RIGHT: u32 = 1 << 22;
+pub const CHANNEL_LOW_FREQUENCY_2: u32 = 1 << 23;
+pub const CHANNEL_FRONT_WIDE_LEFT: u32 = 1 << 24;
+pub const CHANNEL_FRONT_WIDE_RIGHT: u32 = 1 << 25;
+
+pub const CHANNEL_MONO: u32 = CHANNEL_FRONT_LEFT;
+pub const CHANNEL_STEREO: u32 = CHANNEL_FRONT_LEFT | CHANNEL_FRONT_RIGHT;
+pub const CHANNEL_2POINT1: u32 = CHANNEL_FRONT_LEFT | CHANNEL_FRONT_RIGHT | CHANNEL_LOW_FREQUENCY;
+pub const CHANNEL_TRI: u32 = CHANNEL_FRONT_LEFT | CHANNEL_FRONT_RIGHT | CHANNEL_FRONT_CENTER;
+pub const CHANNEL_TRI_BACK: u32 = CHANNEL_FRONT_LEFT | CHANNEL_FRONT_RIGHT | CHANNEL_BACK_CENTER;
+pub const CHANNEL_3POINT1: u32 =
+    CHANNEL_FRONT_LEFT | CHANNEL_FRONT_RIGHT | CHANNEL_FRONT_CENTER | CHANNEL_LOW_FREQUENCY;
+pub const CHANNEL_2POINT0POINT2: u32 =
+    CHANNEL_FRONT_LEFT | CHANNEL_FRONT_RIGHT | CHANNEL_TOP_SIDE_LEFT | CHANNEL_TOP_SIDE_RIGHT;
+pub const CHANNEL_2POINT1POINT2: u32 = CHANNEL_2POINT0POINT2 | CHANNEL_LOW_FREQUENCY;
+pub const CHANNEL_3POINT0POINT2: u32 = CHANNEL_2POINT0POINT2 | CHANNEL_FRONT_CENTER;
+pub const CHANNEL_3POINT1POINT2: u32 = CHANNEL_3POINT0POINT2 | CHANNEL_LOW_FREQUENCY;
+pub const CHANNEL_QUAD: u32 =
+    CHANNEL_FRONT_LEFT | CHANNEL_FRONT_RIGHT | CHANNEL_BACK_LEFT | CHANNEL_BACK_RIGHT;
+pub const CHANNEL_QUAD_SIDE: u32 =
+    CHANNEL_FRONT_LEFT | CHANNEL_FRONT_RIGHT | CHANNEL_SIDE_LEFT | CHANNEL_SIDE_RIGHT;
+pub const CHANNEL_SURROUND: u32 =
+    CHANNEL_FRONT_LEFT | CHANNEL_FRONT_RIGHT | CHANNEL_FRONT_CENTER | CHANNEL_BACK_CENTER;
+pub const CHANNEL_PENTA: u32 = CHANNEL_QUAD | CHANNEL_FRONT_CENTER;
+pub const CHANNEL_5POINT1: u32 = CHANNEL_FRONT_LEFT
+    | CHANNEL_FRONT_RIGHT
+    | CHANNEL_FRONT_CENTER
+    | CHANNEL_LOW_FREQUENCY
+    | CHANNEL_BACK_LEFT
+    | CHANNEL_BACK_RIGHT;
+pub const CHANNEL_5POINT1_SIDE: u32 = CHANNEL_FRONT_LEFT
+    | CHANNEL_FRONT_RIGHT
+    | CHANNEL_FRONT_CENTER
+    | CHANNEL_LOW_FREQUENCY
+    | CHANNEL_SIDE_LEFT
+    | CHANNEL_SIDE_RIGHT;
+pub const CHANNEL_6POINT1: u32 = CHANNEL_5POINT1 | CHANNEL_BACK_CENTER;
+pub const CHANNEL_7POINT1: u32 = CHANNEL_5POINT1 | CHANNEL_SIDE_LEFT | CHANNEL_SIDE_RIGHT;
+pub const CHANNEL_5POINT1POINT2: u32 =
+    CHANNEL_5POINT1 | CHANNEL_TOP_SIDE_LEFT | CHANNEL_TOP_SIDE_RIGHT;
+pub const CHANNEL_5POINT1POINT4: u32 = CHANNEL_5POINT1
+    | CHANNEL_TOP_FRONT_LEFT
+    | CHANNEL_TOP_FRONT_RIGHT
+    | CHANNEL_TOP_BACK_LEFT
+    | CHANNEL_TOP_BACK_RIGHT;
+pub const CHANNEL_7POINT1POINT2: u32 =
+    CHANNEL_7POINT1 | CHANNEL_TOP_SIDE_LEFT | CHANNEL_TOP_SIDE_RIGHT;
+pub const CHANNEL_7POINT1POINT4: u32 = CHANNEL_7POINT1
+    | CHANNEL_TOP_FRONT_LEFT
+    | CHANNEL_TOP_FRONT_RIGHT
+    | CHANNEL_TOP_BACK_LEFT
+    | CHANNEL_TOP_BACK_RIGHT;
+pub const CHANNEL_9POINT1POINT4: u32 =
+    CHANNEL_7POINT1POINT4 | CHANNEL_FRONT_WIDE_LEFT | CHANNEL_FRONT_WIDE_RIGHT;
+pub const CHANNEL_9POINT1POINT6: u32 =
+    CHANNEL_9POINT1POINT4 | CHANNEL_TOP_SIDE_LEFT | CHANNEL_TOP_SIDE_RIGHT;
+pub const CHANNEL_FRONT_BACK: u32 = CHANNEL_FRONT_CENTER | CHANNEL_BACK_CENTER;
+
 pub const OK: i32 = 0;
 
 const ERROR_BASE: i32 = -900;
@@ -185,6 +314,33 @@ pub type DataCallback = Option<
     ) -> i32,
 >;
 
+/// Prototype for the data function that is passed to AAudioStreamBuilder_setPartialDataCallback().
+///
+/// This is the same as `DataCallback`, except that the function may process fewer frames
+/// than requested and returns the number of frames it actually processed.
+///
+/// For an output stream, the frames that were not written are requested again
+/// in the next callback. For an input stream, the frames that were not read are passed again
+/// in the next callback.
+///
+/// The restrictions on `DataCallback` apply to this function too.
+///
+/// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+/// * `user_data` - the same address that was passed to AAudioStreamBuilder_setPartialDataCallback()
+/// * `audio_data` - a pointer to the audio data
+/// * `num_frames` - the number of frames to be processed, which can vary
+///
+/// Returns the number of frames processed, between 0 and `num_frames`.
+/// The stream is stopped if the returned value is negative or greater than `num_frames`.
+pub type PartialDataCallback = Option<
+    unsafe extern "C" fn(
+        stream: *mut AAudioStream,
+        user_data: *mut c_void,
+        audio_data: *mut c_void,
+        num_frames: i32,
+    ) -> i32,
+>;
+
 /// Prototype for the callback function that is passed to
 /// AAudioStreamBuilder_setErrorCallback().
 ///
@@ -206,8 +362,32 @@ pub type DataCallback = Option<
 pub type ErrorCallback =
     Option<unsafe extern "C" fn(stream: *mut AAudioStream, user_data: *mut c_void, error: i32)>;
 
-#[link(name = "aaudio")]
+/// Prototype for the callback function that is passed to
+/// AAudioStreamBuilder_setPresentationEndCallback().
+///
+/// This will be called when all the data of an offloaded stream that was queued in the audio
+/// system, eg. the combination of the Android audio framework and the audio hardware, has been
+/// played after AAudioStream_setOffloadEndOfStream() was called.
+///
+/// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+/// * `user_data` - the same address that was passed to
+///   AAudioStreamBuilder_setPresentationEndCallback()
+pub type PresentationEndCallback =
+    Option<unsafe extern "C" fn(stream: *mut AAudioStream, user_data: *mut c_void)>;
+
+#[cfg(not(any(feature = "mock", feature = "dynamic")))]
+#[cfg_attr(target_os = "android", link(name = "aaudio"))]
 extern "C" {
+    /// The text is the ASCII symbol corresponding to the return code,
+    /// or an English message saying the return code is unrecognized.
+    /// This is intended for developers to use when debugging.
+    /// It is not for display to users.
+    ///
+    /// Available since API level 26.
+    ///
+    /// Returns a pointer to a text representation of an AAudio result code.
+    pub fn AAudio_convertResultToText(return_code: i32) -> *const libc::c_char;
+
     /// Create a StreamBuilder that can be used to open a Stream.
     ///
     /// The deviceId is initially unspecified, meaning that the current default device will be used.
@@ -275,6 +455,29 @@ extern "C" {
         channel_count: i32,
     );
 
+    /// Set audio channel mask for the stream.
+    ///
+    /// The default, if you do not call this function, is `UNSPECIFIED`.
+    /// If both channel mask and count are not set, then stereo will then be chosen when the
+    /// stream is opened.
+    /// After opening a stream with an unspecified value, the application must query for the
+    /// actual value, which may vary by device.
+    ///
+    /// If an exact value is specified then an opened stream will use that value.
+    /// If a stream cannot be opened with the specified value then the open will fail.
+    ///
+    /// As the corresponding channel count of the provided channel mask here may be different
+    /// from the channel count used in AAudioStreamBuilder_setChannelCount(),
+    /// the last called function will be respected if both functions are called.
+    ///
+    /// Available since API level 32.
+    ///
+    /// # Arguments
+    ///
+    /// * `builder` - reference provided by AAudio_createStreamBuilder()
+    /// * `channel_mask` - Audio channel mask desired, one of the `CHANNEL_*` values.
+    pub fn AAudioStreamBuilder_setChannelMask(builder: *mut AAudioStreamBuilder, channel_mask: u32);
+
     /// Request a sample data format, for example `FORMAT_PCM_I16`.
     ///
     /// The default, if you do not call this function, is `UNSPECIFIED`.
@@ -470,6 +673,84 @@ extern "C" {
         privacy_sensitive: bool,
     );
 
+    /// Sets the behavior affecting whether spatialization will be used.
+    ///
+    /// The AAudio system will use this information to select whether the stream will go
+    /// through a spatializer effect or not when the effect is supported and enabled.
+    ///
+    /// The default is `SPATIALIZATION_BEHAVIOR_AUTO`.
+    ///
+    /// Available since API level 32.
+    ///
+    /// # Arguments
+    ///
+    /// * `builder` - reference provided by AAudio_createStreamBuilder()
+    /// * `spatialization_behavior` - the desired behavior with regards to spatialization,
+    ///   eg. `SPATIALIZATION_BEHAVIOR_AUTO`
+    pub fn AAudioStreamBuilder_setSpatializationBehavior(
+        builder: *mut AAudioStreamBuilder,
+        spatialization_behavior: i32,
+    );
+
+    /// Specifies whether the audio data of this output stream has already been processed for
+    /// spatialization.
+    ///
+    /// If the stream has been processed for spatialization, setting this to true will prevent
+    /// issues such as double-processing on platforms that will spatialize audio data.
+    ///
+    /// The default is false.
+    ///
+    /// Available since API level 32.
+    ///
+    /// # Arguments
+    ///
+    /// * `builder` - reference provided by AAudio_createStreamBuilder()
+    /// * `is_spatialized` - true if the content is already processed for binaural or transaural
+    ///   spatial rendering, false otherwise.
+    pub fn AAudioStreamBuilder_setIsContentSpatialized(
+        builder: *mut AAudioStreamBuilder,
+        is_spatialized: bool,
+    );
+
+    /// Declare the name of the package creating the stream.
+    ///
+    /// This is usually Context#getPackageName().
+    ///
+    /// The default, if you do not call this function, is a random package in the calling uid.
+    /// The vast majority of apps have only one package per calling UID.
+    /// If an invalid package name is set, input streams may not be given permission to
+    /// record when started.
+    ///
+    /// The package name is usually the applicationId in your app's build.gradle file.
+    ///
+    /// Available since API level 31.
+    ///
+    /// # Arguments
+    ///
+    /// * `builder` - reference provided by AAudio_createStreamBuilder()
+    /// * `package_name` - packageName of the calling app.
+    pub fn AAudioStreamBuilder_setPackageName(
+        builder: *mut AAudioStreamBuilder,
+        package_name: *const libc::c_char,
+    );
+
+    /// Declare the attribution tag of the context creating the stream.
+    ///
+    /// This is usually Context#getAttributionTag().
+    ///
+    /// The default, if you do not call this function, is null.
+    ///
+    /// Available since API level 31.
+    ///
+    /// # Arguments
+    ///
+    /// * `builder` - reference provided by AAudio_createStreamBuilder()
+    /// * `attribution_tag` - attributionTag of the calling context.
+    pub fn AAudioStreamBuilder_setAttributionTag(
+        builder: *mut AAudioStreamBuilder,
+        attribution_tag: *const libc::c_char,
+    );
+
     /// Request that AAudio call this functions when the stream is running.
     ///
     /// Note that when using this callback, the audio data will be passed in or out
@@ -492,13 +773,32 @@ extern "C" {
     /// * `builder` - reference provided by AAudio_createStreamBuilder()
     /// * `callback` - pointer to a function that will process audio data.
     /// * `user_data` - pointer to an application data structure that will be passed
-    ///          to the callback functions.
+    ///   to the callback functions.
     pub fn AAudioStreamBuilder_setDataCallback(
         builder: *mut AAudioStreamBuilder,
         callback: DataCallback,
         user_data: *mut c_void,
     );
 
+    /// Request that AAudio call this function when the stream is running, letting it process
+    /// fewer frames than requested. See `PartialDataCallback`.
+    ///
+    /// This is an alternative to AAudioStreamBuilder_setDataCallback(). Only one of
+    /// the two data callbacks may be set, otherwise opening the stream fails with
+    /// `ERROR_ILLEGAL_ARGUMENT`. Pass a null callback to clear the other one.
+    ///
+    /// Available since API level 36.
+    ///
+    /// * `builder` - reference provided by AAudio_createStreamBuilder()
+    /// * `callback` - pointer to a function that will process audio data.
+    /// * `user_data` - pointer to an application data structure that will be passed
+    ///   to the callback functions.
+    pub fn AAudioStreamBuilder_setPartialDataCallback(
+        builder: *mut AAudioStreamBuilder,
+        callback: PartialDataCallback,
+        user_data: *mut c_void,
+    );
+
     /// Set the requested data callback buffer size in frames.
     /// See `DataCallback`.
     ///
@@ -549,13 +849,32 @@ extern "C" {
     /// * `builder` - reference provided by AAudio_createStreamBuilder()
     /// * `callback` - pointer to a function that will be called if an error occurs.
     /// * `user_data` - pointer to an application data structure that will be passed
-    ///          to the callback functions.
+    ///   to the callback functions.
     pub fn AAudioStreamBuilder_setErrorCallback(
         builder: *mut AAudioStreamBuilder,
         callback: ErrorCallback,
         user_data: *mut c_void,
     );
 
+    /// Request that AAudio call this function when the presentation of an offloaded stream
+    /// has ended. See `PresentationEndCallback`.
+    ///
+    /// This is only used for output streams with `PERFORMANCE_MODE_POWER_SAVING_OFFLOADED`.
+    ///
+    /// Available since API level 36.
+    ///
+    /// # Arguments
+    ///
+    /// * `builder` - reference provided by AAudio_createStreamBuilder()
+    /// * `callback` - pointer to a function that will be called when the presentation ends.
+    /// * `user_data` - pointer to an application data structure that will be passed
+    ///   to the callback function.
+    pub fn AAudioStreamBuilder_setPresentationEndCallback(
+        builder: *mut AAudioStreamBuilder,
+        callback: PresentationEndCallback,
+        user_data: *mut c_void,
+    );
+
     /// Open a stream based on the options in the StreamBuilder.
     /// Returns 0 for OK or a negative error.
     ///
@@ -581,7 +900,6 @@ extern "C" {
     /// # Arguments
     ///
     /// * `builder` - reference provided by AAudio_createStreamBuilder()
-    /// @return 0 for OK or a negative error.
     pub fn AAudioStreamBuilder_delete(builder: *mut AAudioStreamBuilder) -> i32;
 
     /// Free the audio resources associated with a stream created by
@@ -789,6 +1107,61 @@ extern "C" {
     /// * `num_frames` - requested number of frames that can be filled without blocking
     pub fn AAudioStream_setBufferSizeInFrames(stream: *mut AAudioStream, num_frames: i32) -> i32;
 
+    /// Declares the delay and the padding of the compressed data played by an offloaded stream,
+    /// so that the decoder can skip the frames added by the encoder for gapless playback.
+    /// Returns 0 for OK or a negative error.
+    ///
+    /// `ERROR_UNIMPLEMENTED` is returned if the stream is not offloaded and
+    /// `ERROR_ILLEGAL_ARGUMENT` if either value is negative.
+    ///
+    /// Available since API level 36.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    /// * `delay_in_frames` - number of frames to skip at the beginning of the track
+    /// * `padding_in_frames` - number of frames to skip at the end of the track
+    pub fn AAudioStream_setOffloadDelayPadding(
+        stream: *mut AAudioStream,
+        delay_in_frames: i32,
+        padding_in_frames: i32,
+    ) -> i32;
+
+    /// Return the delay set with AAudioStream_setOffloadDelayPadding() in frames,
+    /// or a negative error.
+    ///
+    /// Available since API level 36.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_getOffloadDelay(stream: *mut AAudioStream) -> i32;
+
+    /// Return the padding set with AAudioStream_setOffloadDelayPadding() in frames,
+    /// or a negative error.
+    ///
+    /// Available since API level 36.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_getOffloadPadding(stream: *mut AAudioStream) -> i32;
+
+    /// Indicates that all the data of the current track has been written to an offloaded stream.
+    /// Returns 0 for OK or a negative error.
+    ///
+    /// The presentation end callback is called once that data has been played.
+    /// Data written after this call belongs to the next track.
+    ///
+    /// `ERROR_UNIMPLEMENTED` is returned if the stream is not offloaded.
+    ///
+    /// Available since API level 36.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_setOffloadEndOfStream(stream: *mut AAudioStream) -> i32;
+
     /// Query the maximum number of frames that can be filled without blocking.
     ///
     /// Available since API level 26.
@@ -879,6 +1252,52 @@ extern "C" {
     /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
     pub fn AAudioStream_getChannelCount(stream: *mut AAudioStream) -> i32;
 
+    /// Return the channel mask for the stream. This will be the mask set using
+    /// AAudioStreamBuilder_setChannelMask(), or `UNSPECIFIED` otherwise.
+    ///
+    /// Available since API level 32.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_getChannelMask(stream: *mut AAudioStream) -> u32;
+
+    /// Return the actual number of channels of the hardware.
+    ///
+    /// This may differ from the channel count of the stream, in which case the framework
+    /// converts between them.
+    ///
+    /// Available since API level 34.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_getHardwareChannelCount(stream: *mut AAudioStream) -> i32;
+
+    /// Return the actual sample rate of the hardware.
+    ///
+    /// This may differ from the sample rate of the stream, in which case the framework
+    /// resamples the data.
+    ///
+    /// Available since API level 34.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_getHardwareSampleRate(stream: *mut AAudioStream) -> i32;
+
+    /// Return the actual data format of the hardware.
+    ///
+    /// This may differ from the data format of the stream, in which case the framework
+    /// converts between them.
+    ///
+    /// Available since API level 34.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_getHardwareFormat(stream: *mut AAudioStream) -> i32;
+
     /// Available since API level 26.
     /// Returns the actual device ID.
     ///
@@ -971,6 +1390,75 @@ extern "C" {
     /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
     pub fn AAudioStream_getSessionId(stream: *mut AAudioStream) -> i32;
 
+    /// Return the use case for the stream.
+    ///
+    /// Available since API level 28.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_getUsage(stream: *mut AAudioStream) -> i32;
+
+    /// Return the content type for the stream.
+    ///
+    /// Available since API level 28.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_getContentType(stream: *mut AAudioStream) -> i32;
+
+    /// Return the input preset for the stream.
+    ///
+    /// Available since API level 28.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_getInputPreset(stream: *mut AAudioStream) -> i32;
+
+    /// Return the policy that determines whether the audio may or may not be captured
+    /// by other apps or the system.
+    ///
+    /// Available since API level 29.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_getAllowedCapturePolicy(stream: *mut AAudioStream) -> i32;
+
+    /// Return whether this input stream is marked as privacy sensitive or not.
+    ///
+    /// See AAudioStreamBuilder_setPrivacySensitive().
+    ///
+    /// Available since API level 30.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_isPrivacySensitive(stream: *mut AAudioStream) -> bool;
+
+    /// Return the spatialization behavior for the stream.
+    ///
+    /// If none was explicitly set, it will return the default `SPATIALIZATION_BEHAVIOR_AUTO`
+    /// behavior.
+    ///
+    /// Available since API level 32.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_getSpatializationBehavior(stream: *mut AAudioStream) -> i32;
+
+    /// Return whether the content of the stream is spatialized.
+    ///
+    /// Available since API level 32.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - reference provided by AAudioStreamBuilder_openStream()
+    pub fn AAudioStream_isContentSpatialized(stream: *mut AAudioStream) -> bool;
+
     /// Passes back the time at which a particular frame was presented.
     /// This can be used to synchronize audio with video or MIDI.
     /// It can also be used to align a recorded stream with a playback stream.
@@ -0,0 +1,482 @@
+//! Rust implementations of the functions declared in the crate root.
+//! See the declarations there for the documentation.
+
+#![allow(non_snake_case, clippy::missing_safety_doc)]
+
+use std::ffi::c_void;
+
+use super::stream::{Builder, Stream};
+use crate::*;
+
+unsafe fn builder<'a>(builder: *mut AAudioStreamBuilder) -> &'a mut Builder {
+    &mut *(builder as *mut Builder)
+}
+
+unsafe fn stream<'a>(stream: *mut AAudioStream) -> &'a Stream {
+    &*(stream as *const Stream)
+}
+
+pub unsafe extern "C" fn AAudio_convertResultToText(return_code: i32) -> *const libc::c_char {
+    let text: &'static [u8] = match return_code {
+        OK => b"AAUDIO_OK\0",
+        ERROR_DISCONNECTED => b"AAUDIO_ERROR_DISCONNECTED\0",
+        ERROR_ILLEGAL_ARGUMENT => b"AAUDIO_ERROR_ILLEGAL_ARGUMENT\0",
+        ERROR_INTERNAL => b"AAUDIO_ERROR_INTERNAL\0",
+        ERROR_INVALID_STATE => b"AAUDIO_ERROR_INVALID_STATE\0",
+        ERROR_INVALID_HANDLE => b"AAUDIO_ERROR_INVALID_HANDLE\0",
+        ERROR_UNIMPLEMENTED => b"AAUDIO_ERROR_UNIMPLEMENTED\0",
+        ERROR_UNAVAILABLE => b"AAUDIO_ERROR_UNAVAILABLE\0",
+        ERROR_NO_FREE_HANDLES => b"AAUDIO_ERROR_NO_FREE_HANDLES\0",
+        ERROR_NO_MEMORY => b"AAUDIO_ERROR_NO_MEMORY\0",
+        ERROR_NULL => b"AAUDIO_ERROR_NULL\0",
+        ERROR_TIMEOUT => b"AAUDIO_ERROR_TIMEOUT\0",
+        ERROR_WOULD_BLOCK => b"AAUDIO_ERROR_WOULD_BLOCK\0",
+        ERROR_INVALID_FORMAT => b"AAUDIO_ERROR_INVALID_FORMAT\0",
+        ERROR_OUT_OF_RANGE => b"AAUDIO_ERROR_OUT_OF_RANGE\0",
+        ERROR_NO_SERVICE => b"AAUDIO_ERROR_NO_SERVICE\0",
+        ERROR_INVALID_RATE => b"AAUDIO_ERROR_INVALID_RATE\0",
+        _ => b"Unrecognized AAudio error.\0",
+    };
+    text.as_ptr() as *const libc::c_char
+}
+
+pub unsafe extern "C" fn AAudio_createStreamBuilder(builder: *mut *mut AAudioStreamBuilder) -> i32 {
+    if builder.is_null() {
+        return ERROR_NULL;
+    }
+    *builder = Box::into_raw(Box::<Builder>::default()) as *mut AAudioStreamBuilder;
+    OK
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setDeviceId(
+    b: *mut AAudioStreamBuilder,
+    device_id: i32,
+) {
+    builder(b).device_id = device_id;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setSampleRate(
+    b: *mut AAudioStreamBuilder,
+    sample_rate: i32,
+) {
+    builder(b).sample_rate = sample_rate;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setChannelCount(
+    b: *mut AAudioStreamBuilder,
+    channel_count: i32,
+) {
+    // The last of the channel count and the channel mask is respected.
+    let builder = builder(b);
+    builder.channel_count = channel_count;
+    builder.channel_mask = UNSPECIFIED as u32;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setChannelMask(
+    b: *mut AAudioStreamBuilder,
+    channel_mask: u32,
+) {
+    let builder = builder(b);
+    builder.channel_mask = channel_mask;
+    builder.channel_count = UNSPECIFIED;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setFormat(b: *mut AAudioStreamBuilder, format: i32) {
+    builder(b).format = format;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setSharingMode(
+    b: *mut AAudioStreamBuilder,
+    sharing_mode: i32,
+) {
+    builder(b).sharing_mode = sharing_mode;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setDirection(
+    b: *mut AAudioStreamBuilder,
+    direction: i32,
+) {
+    builder(b).direction = direction;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setBufferCapacityInFrames(
+    b: *mut AAudioStreamBuilder,
+    num_frames: i32,
+) {
+    builder(b).buffer_capacity = num_frames;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setPerformanceMode(
+    b: *mut AAudioStreamBuilder,
+    mode: i32,
+) {
+    builder(b).performance_mode = mode;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setUsage(b: *mut AAudioStreamBuilder, usage: i32) {
+    builder(b).usage = usage;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setContentType(
+    b: *mut AAudioStreamBuilder,
+    content_type: i32,
+) {
+    builder(b).content_type = content_type;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setInputPreset(
+    b: *mut AAudioStreamBuilder,
+    input_preset: i32,
+) {
+    builder(b).input_preset = input_preset;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setAllowedCapturePolicy(
+    b: *mut AAudioStreamBuilder,
+    capture_policy: i32,
+) {
+    builder(b).capture_policy = capture_policy;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setSessionId(
+    b: *mut AAudioStreamBuilder,
+    session_id: i32,
+) {
+    builder(b).session_id = session_id;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setPrivacySensitive(
+    b: *mut AAudioStreamBuilder,
+    privacy_sensitive: bool,
+) {
+    builder(b).privacy_sensitive = Some(privacy_sensitive);
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setSpatializationBehavior(
+    b: *mut AAudioStreamBuilder,
+    spatialization_behavior: i32,
+) {
+    builder(b).spatialization_behavior = spatialization_behavior;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setIsContentSpatialized(
+    b: *mut AAudioStreamBuilder,
+    is_spatialized: bool,
+) {
+    builder(b).content_spatialized = is_spatialized;
+}
+
+/// The virtual device does not check permissions. The package name is recorded when
+/// the stream is opened, see `mock::last_attribution()`.
+pub unsafe extern "C" fn AAudioStreamBuilder_setPackageName(
+    b: *mut AAudioStreamBuilder,
+    package_name: *const libc::c_char,
+) {
+    builder(b).package_name = package_name;
+}
+
+/// The virtual device does not check permissions. The attribution tag is recorded when
+/// the stream is opened, see `mock::last_attribution()`.
+pub unsafe extern "C" fn AAudioStreamBuilder_setAttributionTag(
+    b: *mut AAudioStreamBuilder,
+    attribution_tag: *const libc::c_char,
+) {
+    builder(b).attribution_tag = attribution_tag;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setDataCallback(
+    b: *mut AAudioStreamBuilder,
+    callback: DataCallback,
+    user_data: *mut c_void,
+) {
+    let builder = builder(b);
+    builder.data_callback = callback;
+    builder.data_user_data = user_data;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setPartialDataCallback(
+    b: *mut AAudioStreamBuilder,
+    callback: PartialDataCallback,
+    user_data: *mut c_void,
+) {
+    let builder = builder(b);
+    builder.partial_data_callback = callback;
+    builder.partial_data_user_data = user_data;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setFramesPerDataCallback(
+    b: *mut AAudioStreamBuilder,
+    num_frames: i32,
+) {
+    builder(b).frames_per_data_callback = num_frames;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setErrorCallback(
+    b: *mut AAudioStreamBuilder,
+    callback: ErrorCallback,
+    user_data: *mut c_void,
+) {
+    let builder = builder(b);
+    builder.error_callback = callback;
+    builder.error_user_data = user_data;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_setPresentationEndCallback(
+    b: *mut AAudioStreamBuilder,
+    callback: PresentationEndCallback,
+    user_data: *mut c_void,
+) {
+    let builder = builder(b);
+    builder.presentation_end_callback = callback;
+    builder.presentation_end_user_data = user_data;
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_openStream(
+    b: *mut AAudioStreamBuilder,
+    stream: *mut *mut AAudioStream,
+) -> i32 {
+    if b.is_null() || stream.is_null() {
+        return ERROR_NULL;
+    }
+    match Stream::open(builder(b)) {
+        Ok(raw) => {
+            *stream = raw as *mut AAudioStream;
+            OK
+        }
+        Err(error) => error,
+    }
+}
+
+pub unsafe extern "C" fn AAudioStreamBuilder_delete(b: *mut AAudioStreamBuilder) -> i32 {
+    if b.is_null() {
+        return ERROR_NULL;
+    }
+    drop(Box::from_raw(b as *mut Builder));
+    OK
+}
+
+pub unsafe extern "C" fn AAudioStream_release(s: *mut AAudioStream) -> i32 {
+    stream(s).release()
+}
+
+pub unsafe extern "C" fn AAudioStream_close(s: *mut AAudioStream) -> i32 {
+    if s.is_null() {
+        return ERROR_NULL;
+    }
+    Stream::close(s as *mut Stream)
+}
+
+pub unsafe extern "C" fn AAudioStream_requestStart(s: *mut AAudioStream) -> i32 {
+    stream(s).request_start()
+}
+
+pub unsafe extern "C" fn AAudioStream_requestPause(s: *mut AAudioStream) -> i32 {
+    stream(s).request_pause()
+}
+
+pub unsafe extern "C" fn AAudioStream_requestFlush(s: *mut AAudioStream) -> i32 {
+    stream(s).request_flush()
+}
+
+pub unsafe extern "C" fn AAudioStream_requestStop(s: *mut AAudioStream) -> i32 {
+    stream(s).request_stop()
+}
+
+pub unsafe extern "C" fn AAudioStream_getState(s: *mut AAudioStream) -> i32 {
+    stream(s).state().stream_state
+}
+
+pub unsafe extern "C" fn AAudioStream_waitForStateChange(
+    s: *mut AAudioStream,
+    input_state: i32,
+    next_state: *mut i32,
+    timeout_nanoseconds: i64,
+) -> i32 {
+    match stream(s).wait_for_state_change(input_state, timeout_nanoseconds) {
+        Ok(state) => {
+            if !next_state.is_null() {
+                *next_state = state;
+            }
+            OK
+        }
+        Err(error) => error,
+    }
+}
+
+pub unsafe extern "C" fn AAudioStream_read(
+    s: *mut AAudioStream,
+    buffer: *mut c_void,
+    num_frames: i32,
+    timeout_nanoseconds: i64,
+) -> i32 {
+    if buffer.is_null() {
+        return ERROR_NULL;
+    }
+    stream(s).read(buffer as *mut u8, num_frames, timeout_nanoseconds)
+}
+
+pub unsafe extern "C" fn AAudioStream_write(
+    s: *mut AAudioStream,
+    buffer: *const c_void,
+    num_frames: i32,
+    timeout_nanoseconds: i64,
+) -> i32 {
+    if buffer.is_null() {
+        return ERROR_NULL;
+    }
+    stream(s).write(buffer as *const u8, num_frames, timeout_nanoseconds)
+}
+
+pub unsafe extern "C" fn AAudioStream_setBufferSizeInFrames(
+    s: *mut AAudioStream,
+    num_frames: i32,
+) -> i32 {
+    stream(s).set_buffer_size(num_frames)
+}
+
+pub unsafe extern "C" fn AAudioStream_setOffloadDelayPadding(
+    s: *mut AAudioStream,
+    delay_in_frames: i32,
+    padding_in_frames: i32,
+) -> i32 {
+    stream(s).set_offload_delay_padding(delay_in_frames, padding_in_frames)
+}
+
+pub unsafe extern "C" fn AAudioStream_getOffloadDelay(s: *mut AAudioStream) -> i32 {
+    stream(s)
+        .offload_delay_padding()
+        .map_or_else(|e| e, |(delay, _)| delay)
+}
+
+pub unsafe extern "C" fn AAudioStream_getOffloadPadding(s: *mut AAudioStream) -> i32 {
+    stream(s)
+        .offload_delay_padding()
+        .map_or_else(|e| e, |(_, padding)| padding)
+}
+
+pub unsafe extern "C" fn AAudioStream_setOffloadEndOfStream(s: *mut AAudioStream) -> i32 {
+    stream(s).set_offload_end_of_stream()
+}
+
+pub unsafe extern "C" fn AAudioStream_getBufferSizeInFrames(s: *mut AAudioStream) -> i32 {
+    stream(s).state().buffer_size
+}
+
+pub unsafe extern "C" fn AAudioStream_getFramesPerBurst(s: *mut AAudioStream) -> i32 {
+    stream(s).config.frames_per_burst
+}
+
+pub unsafe extern "C" fn AAudioStream_getBufferCapacityInFrames(s: *mut AAudioStream) -> i32 {
+    stream(s).config.buffer_capacity
+}
+
+pub unsafe extern "C" fn AAudioStream_getFramesPerDataCallback(s: *mut AAudioStream) -> i32 {
+    stream(s).config.frames_per_data_callback
+}
+
+pub unsafe extern "C" fn AAudioStream_getXRunCount(s: *mut AAudioStream) -> i32 {
+    stream(s).state().xrun_count
+}
+
+pub unsafe extern "C" fn AAudioStream_getSampleRate(s: *mut AAudioStream) -> i32 {
+    stream(s).config.sample_rate
+}
+
+pub unsafe extern "C" fn AAudioStream_getChannelCount(s: *mut AAudioStream) -> i32 {
+    stream(s).config.channel_count
+}
+
+pub unsafe extern "C" fn AAudioStream_getChannelMask(s: *mut AAudioStream) -> u32 {
+    stream(s).config.channel_mask
+}
+
+pub unsafe extern "C" fn AAudioStream_getHardwareChannelCount(s: *mut AAudioStream) -> i32 {
+    stream(s).config.hardware_channel_count
+}
+
+pub unsafe extern "C" fn AAudioStream_getHardwareSampleRate(s: *mut AAudioStream) -> i32 {
+    stream(s).config.hardware_sample_rate
+}
+
+pub unsafe extern "C" fn AAudioStream_getHardwareFormat(s: *mut AAudioStream) -> i32 {
+    stream(s).config.hardware_format
+}
+
+pub unsafe extern "C" fn AAudioStream_getDeviceId(s: *mut AAudioStream) -> i32 {
+    stream(s).config.device_id
+}
+
+pub unsafe extern "C" fn AAudioStream_getFormat(s: *mut AAudioStream) -> i32 {
+    stream(s).config.format
+}
+
+pub unsafe extern "C" fn AAudioStream_getSharingMode(s: *mut AAudioStream) -> i32 {
+    stream(s).config.sharing_mode
+}
+
+pub unsafe extern "C" fn AAudioStream_getPerformanceMode(s: *mut AAudioStream) -> i32 {
+    stream(s).config.performance_mode
+}
+
+pub unsafe extern "C" fn AAudioStream_getDirection(s: *mut AAudioStream) -> i32 {
+    stream(s).config.direction
+}
+
+pub unsafe extern "C" fn AAudioStream_getFramesWritten(s: *mut AAudioStream) -> i64 {
+    stream(s).state().frames_written
+}
+
+pub unsafe extern "C" fn AAudioStream_getFramesRead(s: *mut AAudioStream) -> i64 {
+    stream(s).state().frames_read
+}
+
+pub unsafe extern "C" fn AAudioStream_getSessionId(s: *mut AAudioStream) -> i32 {
+    stream(s).config.session_id
+}
+
+pub unsafe extern "C" fn AAudioStream_getUsage(s: *mut AAudioStream) -> i32 {
+    stream(s).config.usage
+}
+
+pub unsafe extern "C" fn AAudioStream_getContentType(s: *mut AAudioStream) -> i32 {
+    stream(s).config.content_type
+}
+
+pub unsafe extern "C" fn AAudioStream_getInputPreset(s: *mut AAudioStream) -> i32 {
+    stream(s).config.input_preset
+}
+
+pub unsafe extern "C" fn AAudioStream_getAllowedCapturePolicy(s: *mut AAudioStream) -> i32 {
+    stream(s).config.capture_policy
+}
+
+pub unsafe extern "C" fn AAudioStream_isPrivacySensitive(s: *mut AAudioStream) -> bool {
+    stream(s).config.privacy_sensitive
+}
+
+pub unsafe extern "C" fn AAudioStream_getSpatializationBehavior(s: *mut AAudioStream) -> i32 {
+    stream(s).config.spatialization_behavior
+}
+
+pub unsafe extern "C" fn AAudioStream_isContentSpatialized(s: *mut AAudioStream) -> bool {
+    stream(s).config.content_spatialized
+}
+
+pub unsafe extern "C" fn AAudioStream_getTimestamp(
+    s: *mut AAudioStream,
+    clockid: libc::clockid_t,
+    frame_position: *mut i64,
+    time_nanoseconds: *mut i64,
+) -> i32 {
+    if frame_position.is_null() || time_nanoseconds.is_null() {
+        return ERROR_NULL;
+    }
+    match stream(s).timestamp(clockid) {
+        Ok((position, time)) => {
+            *frame_position = position;
+            *time_nanoseconds = time;
+            OK
+        }
+        Err(error) => error,
+    }
+}
@@ -0,0 +1,141 @@
+//! A simulated AAudio backend, enabled with the `mock` feature.
+//!
+//! When this feature is enabled, every `AAudio*` function of this crate is implemented
+//! in Rust on top of a virtual device instead of being linked from `libaaudio`, so code
+//! that uses AAudio can be built and tested on hosts without an Android device.
+//!
+//! Streams are driven by a real thread that advances at the device rate: it invokes the
+//! data callback once per burst, or consumes (for output) and produces silence (for input)
+//! for streams that use blocking `AAudioStream_read()` and `AAudioStream_write()`.
+//!
+//! Parameters that are left unspecified on the builder are taken from the current
+//! [`MockDevice`]. Parameters that are requested explicitly are always granted.
+
+use std::sync::Mutex;
+
+pub(crate) mod api;
+mod stream;
+
+use self::stream::Stream;
+
+/// Configuration of the virtual device that backs streams opened in mock mode.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MockDevice {
+    /// Device ID reported by the streams.
+    pub device_id: i32,
+
+    /// Sample rate used when the builder does not request one.
+    pub sample_rate: i32,
+
+    /// Channel count used when the builder does not request one.
+    pub channel_count: i32,
+
+    /// Data format used when the builder does not request one, eg. `FORMAT_PCM_FLOAT`.
+    pub format: i32,
+
+    /// Number of frames the device processes at once.
+    /// The data callback is invoked with this many frames unless
+    /// `AAudioStreamBuilder_setFramesPerDataCallback()` was called.
+    pub frames_per_burst: i32,
+
+    /// Buffer capacity used when the builder does not request one.
+    pub buffer_capacity_in_frames: i32,
+
+    /// Offset in bytes of the buffer passed to the data callback from an 8-byte boundary.
+    /// A non-zero offset simulates a misaligned buffer.
+    pub callback_buffer_offset: usize,
+}
+
+impl MockDevice {
+    const DEFAULT: MockDevice = MockDevice {
+        device_id: 1,
+        sample_rate: 48000,
+        channel_count: 2,
+        format: crate::FORMAT_PCM_FLOAT,
+        frames_per_burst: 192,
+        buffer_capacity_in_frames: 192 * 8,
+        callback_buffer_offset: 0,
+    };
+}
+
+impl Default for MockDevice {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+static DEVICE: Mutex<MockDevice> = Mutex::new(MockDevice::DEFAULT);
+
+/// Streams that are currently open, used to deliver simulated device events.
+static STREAMS: Mutex<Vec<StreamPtr>> = Mutex::new(Vec::new());
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct StreamPtr(*const Stream);
+
+unsafe impl Send for StreamPtr {}
+
+/// Returns the configuration of the virtual device.
+pub fn device() -> MockDevice {
+    *DEVICE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Replaces the configuration of the virtual device.
+///
+/// Only streams opened after this call are affected.
+pub fn set_device(device: MockDevice) {
+    *DEVICE.lock().unwrap_or_else(|e| e.into_inner()) = device;
+}
+
+/// Simulates disconnection of the device, for example when headphones are unplugged.
+///
+/// Every open stream moves to `STREAM_STATE_DISCONNECTED`, stops processing data and,
+/// if it has an error callback, receives `ERROR_DISCONNECTED` on a separate thread,
+/// the same way AAudio reports it.
+pub fn disconnect_all() {
+    let streams = STREAMS.lock().unwrap_or_else(|e| e.into_inner());
+    for stream in streams.iter() {
+        unsafe { (*stream.0).disconnect() };
+    }
+}
+
+/// Simulates an error other than a disconnect, eg. `ERROR_TIMEOUT`.
+///
+/// Every open stream that has an error callback receives `error` on a separate thread,
+/// without changing its state.
+pub fn fail_all(error: i32) {
+    let streams = STREAMS.lock().unwrap_or_else(|e| e.into_inner());
+    for stream in streams.iter() {
+        unsafe { (*stream.0).fail(error) };
+    }
+}
+
+/// The package name and the attribution tag of the last stream opened.
+static ATTRIBUTION: Mutex<(Option<String>, Option<String>)> = Mutex::new((None, None));
+
+/// Returns the package name and the attribution tag that the builder of the last stream
+/// opened was given, read from the builder when the stream was opened.
+pub fn last_attribution() -> (Option<String>, Option<String>) {
+    ATTRIBUTION
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+fn record_attribution(config: &stream::Config) {
+    *ATTRIBUTION.lock().unwrap_or_else(|e| e.into_inner()) =
+        (config.package_name.clone(), config.attribution_tag.clone());
+}
+
+fn register(stream: *const Stream) {
+    STREAMS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(StreamPtr(stream));
+}
+
+fn unregister(stream: *const Stream) {
+    STREAMS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|s| *s != StreamPtr(stream));
+}
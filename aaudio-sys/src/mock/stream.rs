@@ -0,0 +1,802 @@
+use std::collections::VecDeque;
+use std::ffi::{c_void, CStr};
+use std::slice;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// Requested stream parameters, the backing object of `AAudioStreamBuilder`.
+#[derive(Clone)]
+pub(crate) struct Builder {
+    pub device_id: i32,
+    pub sample_rate: i32,
+    pub channel_count: i32,
+    pub channel_mask: u32,
+    pub format: i32,
+    pub sharing_mode: i32,
+    pub direction: i32,
+    pub buffer_capacity: i32,
+    pub performance_mode: i32,
+    pub usage: i32,
+    pub content_type: i32,
+    pub input_preset: i32,
+    pub capture_policy: i32,
+    pub session_id: i32,
+    /// `None` if not set, in which case it depends on the input preset.
+    pub privacy_sensitive: Option<bool>,
+    pub spatialization_behavior: i32,
+    pub content_spatialized: bool,
+    pub frames_per_data_callback: i32,
+    pub data_callback: DataCallback,
+    pub data_user_data: *mut c_void,
+    pub partial_data_callback: PartialDataCallback,
+    pub partial_data_user_data: *mut c_void,
+    pub error_callback: ErrorCallback,
+    pub error_user_data: *mut c_void,
+    pub presentation_end_callback: PresentationEndCallback,
+    pub presentation_end_user_data: *mut c_void,
+    /// Like the pointers to the callbacks, the strings are only read when the stream is opened.
+    pub package_name: *const libc::c_char,
+    pub attribution_tag: *const libc::c_char,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            device_id: UNSPECIFIED,
+            sample_rate: UNSPECIFIED,
+            channel_count: UNSPECIFIED,
+            channel_mask: UNSPECIFIED as u32,
+            format: UNSPECIFIED,
+            sharing_mode: SHARING_SHARED,
+            direction: DIRECTION_OUTPUT,
+            buffer_capacity: UNSPECIFIED,
+            performance_mode: PERFORMANCE_MODE_NONE,
+            usage: USAGE_MEDIA,
+            content_type: CONTENT_TYPE_MUSIC,
+            input_preset: INPUT_PRESET_VOICE_RECOGNITION,
+            capture_policy: ALLOW_CAPTURE_BY_ALL,
+            session_id: SESSION_ID_NONE,
+            privacy_sensitive: None,
+            spatialization_behavior: SPATIALIZATION_BEHAVIOR_AUTO,
+            content_spatialized: false,
+            frames_per_data_callback: UNSPECIFIED,
+            data_callback: None,
+            data_user_data: std::ptr::null_mut(),
+            partial_data_callback: None,
+            partial_data_user_data: std::ptr::null_mut(),
+            error_callback: None,
+            error_user_data: std::ptr::null_mut(),
+            presentation_end_callback: None,
+            presentation_end_user_data: std::ptr::null_mut(),
+            package_name: std::ptr::null(),
+            attribution_tag: std::ptr::null(),
+        }
+    }
+}
+
+pub(crate) fn sample_size(format: i32) -> Option<usize> {
+    match format {
+        FORMAT_PCM_I16 => Some(2),
+        FORMAT_PCM_FLOAT => Some(4),
+        FORMAT_PCM_I24_PACKED => Some(3),
+        FORMAT_PCM_I32 => Some(4),
+        FORMAT_IEC61937 => Some(2),
+        _ => None,
+    }
+}
+
+/// Copies a string passed to the builder, if it was set.
+unsafe fn read_string(string: *const libc::c_char) -> Option<String> {
+    if string.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(string).to_string_lossy().into_owned())
+    }
+}
+
+/// Compressed formats are only accepted by offloaded output streams.
+pub(crate) fn is_compressed(format: i32) -> bool {
+    (FORMAT_MP3..=FORMAT_OPUS).contains(&format)
+}
+
+/// Stream parameters granted when the stream was opened.
+pub(crate) struct Config {
+    pub device_id: i32,
+    pub sample_rate: i32,
+    pub channel_count: i32,
+    pub channel_mask: u32,
+    pub format: i32,
+    pub sharing_mode: i32,
+    pub direction: i32,
+    pub performance_mode: i32,
+    /// The parameters of the virtual device, which the stream is converted from or to.
+    pub hardware_sample_rate: i32,
+    pub hardware_channel_count: i32,
+    pub hardware_format: i32,
+    pub callback_buffer_offset: usize,
+    pub buffer_capacity: i32,
+    pub frames_per_burst: i32,
+    pub frames_per_data_callback: i32,
+    pub session_id: i32,
+    pub usage: i32,
+    pub content_type: i32,
+    pub input_preset: i32,
+    pub capture_policy: i32,
+    pub privacy_sensitive: bool,
+    pub spatialization_behavior: i32,
+    pub content_spatialized: bool,
+    pub package_name: Option<String>,
+    pub attribution_tag: Option<String>,
+    data_callback: DataCallback,
+    data_user_data: *mut c_void,
+    partial_data_callback: PartialDataCallback,
+    partial_data_user_data: *mut c_void,
+    error_callback: ErrorCallback,
+    error_user_data: *mut c_void,
+    presentation_end_callback: PresentationEndCallback,
+    presentation_end_user_data: *mut c_void,
+}
+
+impl Config {
+    fn resolve(builder: &Builder) -> Result<Self, i32> {
+        static NEXT_SESSION_ID: Mutex<i32> = Mutex::new(1);
+
+        let device = super::device();
+        let or_default = |value: i32, default: i32| {
+            if value == UNSPECIFIED {
+                default
+            } else {
+                value
+            }
+        };
+        let format = or_default(builder.format, device.format);
+        let offloaded = builder.direction == DIRECTION_OUTPUT
+            && builder.performance_mode == PERFORMANCE_MODE_POWER_SAVING_OFFLOADED;
+        if sample_size(format).is_none() && !(offloaded && is_compressed(format)) {
+            return Err(ERROR_INVALID_FORMAT);
+        }
+        if builder.sample_rate < 0 {
+            return Err(ERROR_INVALID_RATE);
+        }
+        if builder.channel_count < 0
+            || builder.channel_mask == CHANNEL_INVALID
+            || builder.buffer_capacity < 0
+            || builder.frames_per_data_callback < 0
+            || (builder.data_callback.is_some() && builder.partial_data_callback.is_some())
+        {
+            return Err(ERROR_ILLEGAL_ARGUMENT);
+        }
+        let frames_per_burst = device.frames_per_burst.max(1);
+        let callback_frames = or_default(builder.frames_per_data_callback, frames_per_burst);
+        let buffer_capacity = or_default(builder.buffer_capacity, device.buffer_capacity_in_frames)
+            .max(frames_per_burst)
+            .max(callback_frames * 2);
+        let performance_mode = if builder.direction == DIRECTION_INPUT
+            && (builder.performance_mode == PERFORMANCE_MODE_POWER_SAVING
+                || builder.performance_mode == PERFORMANCE_MODE_POWER_SAVING_OFFLOADED)
+        {
+            PERFORMANCE_MODE_NONE
+        } else {
+            builder.performance_mode
+        };
+        let session_id = if builder.session_id == SESSION_ID_ALLOCATE {
+            let mut next = NEXT_SESSION_ID.lock().unwrap_or_else(|e| e.into_inner());
+            let id = *next;
+            *next += 1;
+            id
+        } else {
+            builder.session_id
+        };
+        // The channel count of a requested mask is the number of its channels.
+        let channel_count = if builder.channel_mask != UNSPECIFIED as u32 {
+            builder.channel_mask.count_ones() as i32
+        } else {
+            or_default(builder.channel_count, device.channel_count)
+        };
+        Ok(Self {
+            device_id: or_default(builder.device_id, device.device_id),
+            sample_rate: or_default(builder.sample_rate, device.sample_rate),
+            channel_count,
+            channel_mask: builder.channel_mask,
+            format,
+            sharing_mode: builder.sharing_mode,
+            direction: builder.direction,
+            performance_mode,
+            hardware_sample_rate: device.sample_rate,
+            hardware_channel_count: device.channel_count,
+            hardware_format: device.format,
+            callback_buffer_offset: device.callback_buffer_offset,
+            buffer_capacity,
+            frames_per_burst,
+            frames_per_data_callback: builder.frames_per_data_callback,
+            session_id,
+            usage: builder.usage,
+            content_type: builder.content_type,
+            input_preset: builder.input_preset,
+            capture_policy: builder.capture_policy,
+            // Voice communication and camcorder recordings are private by default.
+            privacy_sensitive: builder.privacy_sensitive.unwrap_or(
+                builder.direction == DIRECTION_INPUT
+                    && (builder.input_preset == INPUT_PRESET_VOICE_COMMUNICATION
+                        || builder.input_preset == INPUT_PRESET_CAMCORDER),
+            ),
+            spatialization_behavior: builder.spatialization_behavior,
+            content_spatialized: builder.content_spatialized,
+            package_name: unsafe { read_string(builder.package_name) },
+            attribution_tag: unsafe { read_string(builder.attribution_tag) },
+            data_callback: builder.data_callback,
+            data_user_data: builder.data_user_data,
+            partial_data_callback: builder.partial_data_callback,
+            partial_data_user_data: builder.partial_data_user_data,
+            error_callback: builder.error_callback,
+            error_user_data: builder.error_user_data,
+            presentation_end_callback: builder.presentation_end_callback,
+            presentation_end_user_data: builder.presentation_end_user_data,
+        })
+    }
+
+    /// Compressed data is transferred byte by byte.
+    fn frame_size(&self) -> usize {
+        if is_compressed(self.format) {
+            1
+        } else {
+            self.channel_count as usize * sample_size(self.format).unwrap_or(0)
+        }
+    }
+
+    fn has_data_callback(&self) -> bool {
+        self.data_callback.is_some() || self.partial_data_callback.is_some()
+    }
+
+    fn is_offloaded(&self) -> bool {
+        self.performance_mode == PERFORMANCE_MODE_POWER_SAVING_OFFLOADED
+    }
+
+    fn callback_frames(&self) -> i32 {
+        if self.frames_per_data_callback > 0 {
+            self.frames_per_data_callback
+        } else {
+            self.frames_per_burst
+        }
+    }
+}
+
+/// Mutable part of a stream, shared with its device thread.
+pub(crate) struct State {
+    pub stream_state: i32,
+    pub buffer_size: i32,
+    pub frames_written: i64,
+    pub frames_read: i64,
+    pub xrun_count: i32,
+    /// Frame position and the moment it was presented.
+    pub timestamp: Option<(i64, Instant)>,
+    queue: VecDeque<u8>,
+    error_callback_active: bool,
+    offload_delay: i32,
+    offload_padding: i32,
+    /// The frame position of the end of the current track of an offloaded stream.
+    end_of_stream: Option<i64>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait_until<'a>(
+        &self,
+        guard: MutexGuard<'a, State>,
+        deadline: Instant,
+    ) -> MutexGuard<'a, State> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.changed
+            .wait_timeout(guard, timeout)
+            .unwrap_or_else(|e| e.into_inner())
+            .0
+    }
+}
+
+#[derive(Copy, Clone)]
+struct SendPtr<T>(*const T);
+
+unsafe impl<T> Send for SendPtr<T> {}
+
+/// The backing object of `AAudioStream`.
+pub(crate) struct Stream {
+    pub config: Config,
+    shared: Arc<Shared>,
+    /// Serializes control requests and owns the device thread.
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Stream {
+    pub fn open(builder: &Builder) -> Result<*mut Stream, i32> {
+        let config = Config::resolve(builder)?;
+        let state = State {
+            stream_state: STREAM_STATE_OPEN,
+            buffer_size: config.buffer_capacity,
+            frames_written: 0,
+            frames_read: 0,
+            xrun_count: 0,
+            timestamp: None,
+            queue: VecDeque::new(),
+            error_callback_active: false,
+            offload_delay: 0,
+            offload_padding: 0,
+            end_of_stream: None,
+        };
+        let stream = Box::into_raw(Box::new(Stream {
+            config,
+            shared: Arc::new(Shared {
+                state: Mutex::new(state),
+                changed: Condvar::new(),
+            }),
+            worker: Mutex::new(None),
+        }));
+        super::register(stream);
+        super::record_attribution(unsafe { &(*stream).config });
+        Ok(stream)
+    }
+
+    /// Frees a stream returned by `open()`.
+    pub unsafe fn close(stream: *mut Stream) -> i32 {
+        super::unregister(stream);
+        let this = &*stream;
+        this.release();
+        let mut state = this.shared.lock();
+        while state.error_callback_active {
+            state = this
+                .shared
+                .changed
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        state.stream_state = STREAM_STATE_CLOSED;
+        drop(state);
+        drop(Box::from_raw(stream));
+        OK
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, State> {
+        self.shared.lock()
+    }
+
+    fn set_state(&self, state: &mut State, stream_state: i32) {
+        state.stream_state = stream_state;
+        self.shared.changed.notify_all();
+    }
+
+    fn worker(&self) -> MutexGuard<'_, Option<JoinHandle<()>>> {
+        self.worker.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn join(worker: &mut Option<JoinHandle<()>>) {
+        if let Some(handle) = worker.take() {
+            let _ = handle.join();
+        }
+    }
+
+    pub fn release(&self) -> i32 {
+        let mut worker = self.worker();
+        let mut state = self.state();
+        if state.stream_state == STREAM_STATE_CLOSING || state.stream_state == STREAM_STATE_CLOSED {
+            return OK;
+        }
+        self.set_state(&mut state, STREAM_STATE_CLOSING);
+        drop(state);
+        Self::join(&mut worker);
+        OK
+    }
+
+    pub fn request_start(&self) -> i32 {
+        let mut worker = self.worker();
+        match self.state().stream_state {
+            STREAM_STATE_STARTING | STREAM_STATE_STARTED => return OK,
+            STREAM_STATE_DISCONNECTED => return ERROR_DISCONNECTED,
+            STREAM_STATE_CLOSING | STREAM_STATE_CLOSED => return ERROR_INVALID_STATE,
+            _ => {}
+        }
+        // A previous run may still be winding down after a pause or stop request.
+        Self::join(&mut worker);
+        let mut state = self.state();
+        match state.stream_state {
+            STREAM_STATE_OPEN | STREAM_STATE_PAUSED | STREAM_STATE_FLUSHED
+            | STREAM_STATE_STOPPED => {}
+            STREAM_STATE_DISCONNECTED => return ERROR_DISCONNECTED,
+            _ => return ERROR_INVALID_STATE,
+        }
+        self.set_state(&mut state, STREAM_STATE_STARTING);
+        drop(state);
+        let this = SendPtr(self as *const Stream);
+        *worker = Some(thread::spawn(move || {
+            let this = this;
+            // The stream is not freed before the device thread is joined.
+            unsafe { (*this.0).run() }
+        }));
+        OK
+    }
+
+    fn request_halt(&self, transient: i32, halted: i32) -> i32 {
+        let _worker = self.worker();
+        let mut state = self.state();
+        match state.stream_state {
+            STREAM_STATE_STARTING | STREAM_STATE_STARTED => self.set_state(&mut state, transient),
+            STREAM_STATE_DISCONNECTED => return ERROR_DISCONNECTED,
+            STREAM_STATE_CLOSING | STREAM_STATE_CLOSED => return ERROR_INVALID_STATE,
+            s if s == transient || s == halted => {}
+            _ => self.set_state(&mut state, halted),
+        }
+        OK
+    }
+
+    pub fn request_pause(&self) -> i32 {
+        if self.config.direction == DIRECTION_INPUT {
+            return ERROR_UNIMPLEMENTED;
+        }
+        self.request_halt(STREAM_STATE_PAUSING, STREAM_STATE_PAUSED)
+    }
+
+    pub fn request_stop(&self) -> i32 {
+        self.request_halt(STREAM_STATE_STOPPING, STREAM_STATE_STOPPED)
+    }
+
+    pub fn request_flush(&self) -> i32 {
+        if self.config.direction == DIRECTION_INPUT {
+            return ERROR_UNIMPLEMENTED;
+        }
+        let mut worker = self.worker();
+        if self.state().stream_state == STREAM_STATE_PAUSING {
+            Self::join(&mut worker);
+        }
+        let mut state = self.state();
+        match state.stream_state {
+            STREAM_STATE_PAUSED | STREAM_STATE_FLUSHED => {
+                let frame_size = self.config.frame_size();
+                state.frames_read += (state.queue.len() / frame_size) as i64;
+                state.queue.clear();
+                self.set_state(&mut state, STREAM_STATE_FLUSHED);
+                OK
+            }
+            STREAM_STATE_DISCONNECTED => ERROR_DISCONNECTED,
+            _ => ERROR_INVALID_STATE,
+        }
+    }
+
+    pub fn wait_for_state_change(&self, input_state: i32, timeout_nanos: i64) -> Result<i32, i32> {
+        let deadline = Instant::now() + Duration::from_nanos(timeout_nanos.max(0) as u64);
+        let mut state = self.state();
+        while state.stream_state == input_state {
+            if Instant::now() >= deadline {
+                return Err(ERROR_TIMEOUT);
+            }
+            state = self.shared.wait_until(state, deadline);
+        }
+        Ok(state.stream_state)
+    }
+
+    fn check_transfer(&self, direction: i32, num_frames: i32) -> i32 {
+        if self.config.direction != direction {
+            ERROR_UNIMPLEMENTED
+        } else if self.config.has_data_callback() {
+            ERROR_INVALID_STATE
+        } else if num_frames < 0 {
+            ERROR_ILLEGAL_ARGUMENT
+        } else {
+            OK
+        }
+    }
+
+    /// Moves frames between the caller and the stream buffer, waiting for the device
+    /// thread until all frames are transferred or the timeout expires.
+    fn transfer<F>(&self, num_frames: i32, timeout_nanos: i64, mut step: F) -> i32
+    where
+        F: FnMut(&mut State, usize, usize) -> usize,
+    {
+        let deadline = Instant::now() + Duration::from_nanos(timeout_nanos.max(0) as u64);
+        let mut done = 0;
+        let mut state = self.state();
+        loop {
+            match state.stream_state {
+                STREAM_STATE_DISCONNECTED => return ERROR_DISCONNECTED,
+                STREAM_STATE_CLOSING | STREAM_STATE_CLOSED => return ERROR_INVALID_STATE,
+                _ => {}
+            }
+            let frames = step(&mut state, done, num_frames as usize - done);
+            if frames > 0 {
+                done += frames;
+                self.shared.changed.notify_all();
+            }
+            if done == num_frames as usize || Instant::now() >= deadline {
+                return done as i32;
+            }
+            state = self.shared.wait_until(state, deadline);
+        }
+    }
+
+    pub unsafe fn write(&self, buffer: *const u8, num_frames: i32, timeout_nanos: i64) -> i32 {
+        let result = self.check_transfer(DIRECTION_OUTPUT, num_frames);
+        if result != OK {
+            return result;
+        }
+        let frame_size = self.config.frame_size();
+        self.transfer(num_frames, timeout_nanos, |state, done, remaining| {
+            let queued = state.queue.len() / frame_size;
+            let room = (state.buffer_size as usize).saturating_sub(queued);
+            let frames = room.min(remaining);
+            let data =
+                std::slice::from_raw_parts(buffer.add(done * frame_size), frames * frame_size);
+            state.queue.extend(data);
+            state.frames_written += frames as i64;
+            frames
+        })
+    }
+
+    pub unsafe fn read(&self, buffer: *mut u8, num_frames: i32, timeout_nanos: i64) -> i32 {
+        let result = self.check_transfer(DIRECTION_INPUT, num_frames);
+        if result != OK {
+            return result;
+        }
+        let frame_size = self.config.frame_size();
+        self.transfer(num_frames, timeout_nanos, |state, done, remaining| {
+            let frames = (state.queue.len() / frame_size).min(remaining);
+            let data =
+                std::slice::from_raw_parts_mut(buffer.add(done * frame_size), frames * frame_size);
+            for (dst, src) in data
+                .iter_mut()
+                .zip(state.queue.drain(..frames * frame_size))
+            {
+                *dst = src;
+            }
+            state.frames_read += frames as i64;
+            frames
+        })
+    }
+
+    pub fn set_buffer_size(&self, num_frames: i32) -> i32 {
+        let mut state = self.state();
+        let size = num_frames.clamp(1, self.config.buffer_capacity);
+        state.buffer_size = size;
+        self.shared.changed.notify_all();
+        size
+    }
+
+    pub fn set_offload_delay_padding(&self, delay_in_frames: i32, padding_in_frames: i32) -> i32 {
+        if !self.config.is_offloaded() {
+            return ERROR_UNIMPLEMENTED;
+        }
+        if delay_in_frames < 0 || padding_in_frames < 0 {
+            return ERROR_ILLEGAL_ARGUMENT;
+        }
+        let mut state = self.state();
+        state.offload_delay = delay_in_frames;
+        state.offload_padding = padding_in_frames;
+        OK
+    }
+
+    pub fn offload_delay_padding(&self) -> Result<(i32, i32), i32> {
+        if !self.config.is_offloaded() {
+            return Err(ERROR_UNIMPLEMENTED);
+        }
+        let state = self.state();
+        Ok((state.offload_delay, state.offload_padding))
+    }
+
+    pub fn set_offload_end_of_stream(&self) -> i32 {
+        if !self.config.is_offloaded() {
+            return ERROR_UNIMPLEMENTED;
+        }
+        let mut state = self.state();
+        match state.stream_state {
+            STREAM_STATE_STARTING | STREAM_STATE_STARTED => {}
+            STREAM_STATE_DISCONNECTED => return ERROR_DISCONNECTED,
+            _ => return ERROR_INVALID_STATE,
+        }
+        state.end_of_stream = Some(state.frames_written);
+        OK
+    }
+
+    /// Reports to the presentation end callback once the current track has been played.
+    fn check_end_of_stream(&self) {
+        let mut state = self.state();
+        match state.end_of_stream {
+            Some(end) if state.frames_read >= end => state.end_of_stream = None,
+            _ => return,
+        }
+        drop(state);
+        if let Some(callback) = self.config.presentation_end_callback {
+            unsafe {
+                callback(
+                    self as *const Stream as *mut AAudioStream,
+                    self.config.presentation_end_user_data,
+                )
+            };
+        }
+    }
+
+    /// Moves the stream to the disconnected state and reports it to the error callback.
+    pub fn disconnect(&self) {
+        let mut state = self.state();
+        match state.stream_state {
+            STREAM_STATE_DISCONNECTED | STREAM_STATE_CLOSING | STREAM_STATE_CLOSED => return,
+            _ => self.set_state(&mut state, STREAM_STATE_DISCONNECTED),
+        }
+        self.report_error(state, ERROR_DISCONNECTED);
+    }
+
+    /// Reports `error` to the error callback without changing the state of the stream.
+    pub fn fail(&self, error: i32) {
+        let state = self.state();
+        match state.stream_state {
+            STREAM_STATE_DISCONNECTED | STREAM_STATE_CLOSING | STREAM_STATE_CLOSED => {}
+            _ => self.report_error(state, error),
+        }
+    }
+
+    /// Calls the error callback with `error` on a separate thread, unless it is already running.
+    fn report_error(&self, mut state: MutexGuard<'_, State>, error: i32) {
+        let callback = match self.config.error_callback {
+            Some(callback) if !state.error_callback_active => callback,
+            _ => return,
+        };
+        state.error_callback_active = true;
+        drop(state);
+        let this = SendPtr(self as *const Stream);
+        let user_data = SendPtr(self.config.error_user_data as *const c_void);
+        let shared = self.shared.clone();
+        thread::spawn(move || {
+            let (this, user_data) = (this, user_data);
+            // `close()` waits for `error_callback_active` to be reset before freeing the stream.
+            unsafe {
+                callback(
+                    this.0 as *mut AAudioStream,
+                    user_data.0 as *mut c_void,
+                    error,
+                )
+            };
+            shared.lock().error_callback_active = false;
+            shared.changed.notify_all();
+        });
+    }
+
+    /// Body of the device thread, running while the stream is started.
+    fn run(&self) {
+        let config = &self.config;
+        let frame_size = config.frame_size();
+        let frames = config.callback_frames();
+        let period =
+            Duration::from_nanos(frames as u64 * 1_000_000_000 / config.sample_rate.max(1) as u64);
+        // Like AAudio, allocates the buffer with an alignment suitable for every sample format.
+        let offset = config.callback_buffer_offset;
+        let mut buffer = vec![0u64; (offset + frames as usize * frame_size).div_ceil(8)];
+        let data = unsafe {
+            slice::from_raw_parts_mut(
+                (buffer.as_mut_ptr() as *mut u8).add(offset),
+                frames as usize * frame_size,
+            )
+        };
+        let mut next = Instant::now();
+        loop {
+            {
+                let mut state = self.state();
+                match state.stream_state {
+                    STREAM_STATE_STARTING => self.set_state(&mut state, STREAM_STATE_STARTED),
+                    STREAM_STATE_STARTED => {}
+                    STREAM_STATE_PAUSING => return self.set_state(&mut state, STREAM_STATE_PAUSED),
+                    STREAM_STATE_STOPPING => {
+                        return self.set_state(&mut state, STREAM_STATE_STOPPED)
+                    }
+                    _ => return,
+                }
+            }
+
+            if let Some(callback) = config.data_callback {
+                for byte in data.iter_mut() {
+                    *byte = 0;
+                }
+                let result = unsafe {
+                    callback(
+                        self as *const Stream as *mut AAudioStream,
+                        config.data_user_data,
+                        data.as_mut_ptr() as *mut c_void,
+                        frames,
+                    )
+                };
+                let mut state = self.state();
+                state.frames_written += frames as i64;
+                state.frames_read += frames as i64;
+                self.update_timestamp(&mut state);
+                if result == CALLBACK_STOP {
+                    if state.stream_state == STREAM_STATE_STARTED {
+                        self.set_state(&mut state, STREAM_STATE_STOPPED);
+                    }
+                    return;
+                }
+            } else if let Some(callback) = config.partial_data_callback {
+                for byte in data.iter_mut() {
+                    *byte = 0;
+                }
+                let result = unsafe {
+                    callback(
+                        self as *const Stream as *mut AAudioStream,
+                        config.partial_data_user_data,
+                        data.as_mut_ptr() as *mut c_void,
+                        frames,
+                    )
+                };
+                let mut state = self.state();
+                if result < 0 || result > frames {
+                    if state.stream_state == STREAM_STATE_STARTED {
+                        self.set_state(&mut state, STREAM_STATE_STOPPED);
+                    }
+                    return;
+                }
+                state.frames_written += result as i64;
+                state.frames_read += result as i64;
+                self.update_timestamp(&mut state);
+            } else {
+                let mut state = self.state();
+                let bytes = frames as usize * frame_size;
+                if config.direction == DIRECTION_OUTPUT {
+                    if state.queue.len() < bytes {
+                        state.xrun_count += 1;
+                    }
+                    let consumed = bytes.min(state.queue.len());
+                    state.queue.drain(..consumed);
+                    state.frames_read += frames as i64;
+                } else {
+                    state.queue.extend(data.iter());
+                    let limit = state.buffer_size as usize * frame_size;
+                    if state.queue.len() > limit {
+                        let excess = state.queue.len() - limit;
+                        state.queue.drain(..excess);
+                        state.xrun_count += 1;
+                    }
+                    state.frames_written += frames as i64;
+                }
+                self.update_timestamp(&mut state);
+                self.shared.changed.notify_all();
+            }
+            self.check_end_of_stream();
+
+            next += period;
+            let mut state = self.state();
+            while state.stream_state == STREAM_STATE_STARTED && Instant::now() < next {
+                state = self.shared.wait_until(state, next);
+            }
+        }
+    }
+
+    fn update_timestamp(&self, state: &mut State) {
+        let position = if self.config.direction == DIRECTION_OUTPUT {
+            state.frames_read
+        } else {
+            state.frames_written
+        };
+        state.timestamp = Some((position, Instant::now()));
+    }
+
+    pub fn timestamp(&self, clockid: libc::clockid_t) -> Result<(i64, i64), i32> {
+        let state = self.state();
+        let (position, presented) = match (state.stream_state, state.timestamp) {
+            (STREAM_STATE_STARTED, Some(timestamp)) => timestamp,
+            _ => return Err(ERROR_INVALID_STATE),
+        };
+        let mut now = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        if unsafe { libc::clock_gettime(clockid, &mut now) } != 0 {
+            return Err(ERROR_ILLEGAL_ARGUMENT);
+        }
+        let now = now.tv_sec * 1_000_000_000 + now.tv_nsec;
+        Ok((position, now - presented.elapsed().as_nanos() as i64))
+    }
+}
@@ -0,0 +1,154 @@
+use std::ffi::c_void;
+use std::sync::{Mutex, OnceLock, PoisonError, Weak};
+
+use crate::handle::StreamInner;
+use crate::panic::PanicHandler;
+use crate::{
+    callback_buffer, AAudioStreamRaw, CallbackResult, Error, ErrorKind, ResultCode, StreamView,
+};
+
+/// An object receiving the audio data and the errors of a stream,
+/// registered with `AAudioStreamBuilder::set_callback()`.
+///
+/// Unlike the closures of `AAudioStreamBuilder::set_callbacks()`, all methods are called
+/// on the same object, so they can share its state without synchronization.
+pub trait AudioStreamCallback: Send {
+    /// Called when the stream is running to process `num_frames` frames of `audio_data`.
+    ///
+    /// This is called on a real-time thread owned by AAudio, see
+    /// `AAudioStreamBuilder::set_callbacks()` for the restrictions on the data callback.
+    fn on_audio_ready(
+        &mut self,
+        stream: &StreamView<'_>,
+        audio_data: &mut [u8],
+        num_frames: i32,
+    ) -> CallbackResult;
+
+    /// Called when an error occurs or the stream is disconnected, after the stream
+    /// has been stopped and before its audio resources are released.
+    ///
+    /// The data callback is not called while this method runs, even if the stream
+    /// could not be stopped. Errors stopping the stream or releasing its resources
+    /// are passed to the hook set with `AAudioStreamBuilder::set_panic_hook()` or, without
+    /// a hook, kept until taken with `AAudioStream::take_panic()`.
+    ///
+    /// This is called on the worker thread of the stream, so the stream may be
+    /// queried and the method may block.
+    fn on_error_before_close(&mut self, _stream: &StreamView<'_>, _error: &Error) {}
+
+    /// Called after `on_error_before_close()`, once the audio resources of the stream
+    /// have been released with `AAudioStream::release()`. If it is not available,
+    /// before API level 30, the resources are only released when the stream is closed,
+    /// once the `AAudioStream` and all of its handles are dropped.
+    /// The application may open a new stream from this method.
+    ///
+    /// This is called on the same worker thread as `on_error_before_close()`.
+    fn on_error_after_close(&mut self, _error: Error) {}
+}
+
+/// An `AudioStreamCallback` registered with AAudio for both the data and the error callbacks.
+pub(crate) struct CallbackObject {
+    /// Locked by the data callback without blocking, and by the worker thread handling
+    /// an error while the stream is stopped.
+    callback: Mutex<Box<dyn AudioStreamCallback>>,
+    panic: *const PanicHandler,
+    /// The stream that the callback is registered for, set once the stream is opened.
+    stream: OnceLock<Weak<StreamInner>>,
+}
+
+impl CallbackObject {
+    pub(crate) fn new(callback: Box<dyn AudioStreamCallback>, panic: *const PanicHandler) -> Self {
+        Self {
+            callback: Mutex::new(callback),
+            panic,
+            stream: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn attach(&self, stream: Weak<StreamInner>) {
+        let _ = self.stream.set(stream);
+    }
+
+    /// Stops the stream and calls the error methods of the callback on the worker thread.
+    pub(crate) fn handle_error(&self, error: Error) {
+        // The stream is being closed if it cannot be upgraded, and the error is dropped then.
+        let inner = match self.stream.get().and_then(Weak::upgrade) {
+            Some(inner) => inner,
+            None => return,
+        };
+        let worker = inner.worker().clone();
+        worker.run(move || {
+            let object = match inner.callback_object() {
+                Some(object) => object,
+                None => return,
+            };
+            let raw = inner.raw();
+            let panic = inner.panic_handler();
+            // A disconnected stream cannot be stopped, but its callbacks are no longer called.
+            let stopped = inner
+                .request_stop()
+                .and_then(|()| inner.wait_until_stopped(raw, "error_callback"));
+            match stopped {
+                Err(error) if error.result_code() != Some(ResultCode::Disconnected) => {
+                    panic.report_error(error)
+                }
+                _ => {}
+            }
+            let mut callback = object
+                .callback
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            // The stream is kept open by `inner` while the view exists.
+            let stream = unsafe { StreamView::new(raw) };
+            panic.catch_other("on_error_before_close", || {
+                callback.on_error_before_close(&stream, &error)
+            });
+            // Without `AAudioStream::release()`, the resources are released when the stream
+            // is closed, once the `AAudioStream` and its handles are dropped.
+            match inner.release() {
+                Err(error) if !matches!(error.kind(), ErrorKind::FunctionUnavailable(_)) => {
+                    panic.report_error(error)
+                }
+                _ => {}
+            }
+            panic.catch_other("on_error_after_close", || {
+                callback.on_error_after_close(error)
+            });
+        });
+    }
+}
+
+pub(crate) unsafe extern "C" fn raw_callback_data(
+    stream: *mut AAudioStreamRaw,
+    user_data: *mut c_void,
+    audio_data: *mut c_void,
+    num_frames: i32,
+) -> i32 {
+    let stream = StreamView::new(stream);
+    let object = &*(user_data as *const CallbackObject);
+    let panic = &*object.panic;
+    let data = match callback_buffer(&stream, audio_data, num_frames, panic) {
+        Some(data) => data,
+        None => return CallbackResult::Stop.into(),
+    };
+    // The callback is only locked elsewhere while an error is handled.
+    let mut callback = match object.callback.try_lock() {
+        Ok(callback) => callback,
+        Err(_) => return CallbackResult::Stop.into(),
+    };
+    match panic.catch_data(|| callback.on_audio_ready(&stream, data, num_frames)) {
+        Some(result) => result.into(),
+        None => panic.silence(data).into(),
+    }
+}
+
+pub(crate) unsafe extern "C" fn raw_callback_error(
+    stream: *mut AAudioStreamRaw,
+    user_data: *mut c_void,
+    error: i32,
+) {
+    let object = &*(user_data as *const CallbackObject);
+    (*object.panic).catch_other("error_callback", || {
+        object.handle_error(Error::from_code(error, "error_callback").with_stream(stream));
+    });
+}
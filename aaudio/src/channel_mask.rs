@@ -0,0 +1,164 @@
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign};
+
+use crate::ffi;
+
+/// A set of audio channel positions, describing the channel layout of a stream.
+///
+/// A frame contains one sample for each channel of the mask, in the order of the positions
+/// below. The number of channels of a mask is returned by `ChannelMask::channel_count()`.
+///
+/// Added in API level 32.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct ChannelMask(u32);
+
+impl ChannelMask {
+    /// No channel layout was specified.
+    pub const UNSPECIFIED: Self = Self(ffi::UNSPECIFIED as u32);
+    /// Invalid channel mask.
+    pub const INVALID: Self = Self(ffi::CHANNEL_INVALID);
+
+    pub const FRONT_LEFT: Self = Self(ffi::CHANNEL_FRONT_LEFT);
+    pub const FRONT_RIGHT: Self = Self(ffi::CHANNEL_FRONT_RIGHT);
+    pub const FRONT_CENTER: Self = Self(ffi::CHANNEL_FRONT_CENTER);
+    pub const LOW_FREQUENCY: Self = Self(ffi::CHANNEL_LOW_FREQUENCY);
+    pub const BACK_LEFT: Self = Self(ffi::CHANNEL_BACK_LEFT);
+    pub const BACK_RIGHT: Self = Self(ffi::CHANNEL_BACK_RIGHT);
+    pub const FRONT_LEFT_OF_CENTER: Self = Self(ffi::CHANNEL_FRONT_LEFT_OF_CENTER);
+    pub const FRONT_RIGHT_OF_CENTER: Self = Self(ffi::CHANNEL_FRONT_RIGHT_OF_CENTER);
+    pub const BACK_CENTER: Self = Self(ffi::CHANNEL_BACK_CENTER);
+    pub const SIDE_LEFT: Self = Self(ffi::CHANNEL_SIDE_LEFT);
+    pub const SIDE_RIGHT: Self = Self(ffi::CHANNEL_SIDE_RIGHT);
+    pub const TOP_CENTER: Self = Self(ffi::CHANNEL_TOP_CENTER);
+    pub const TOP_FRONT_LEFT: Self = Self(ffi::CHANNEL_TOP_FRONT_LEFT);
+    pub const TOP_FRONT_CENTER: Self = Self(ffi::CHANNEL_TOP_FRONT_CENTER);
+    pub const TOP_FRONT_RIGHT: Self = Self(ffi::CHANNEL_TOP_FRONT_RIGHT);
+    pub const TOP_BACK_LEFT: Self = Self(ffi::CHANNEL_TOP_BACK_LEFT);
+    pub const TOP_BACK_CENTER: Self = Self(ffi::CHANNEL_TOP_BACK_CENTER);
+    pub const TOP_BACK_RIGHT: Self = Self(ffi::CHANNEL_TOP_BACK_RIGHT);
+    pub const TOP_SIDE_LEFT: Self = Self(ffi::CHANNEL_TOP_SIDE_LEFT);
+    pub const TOP_SIDE_RIGHT: Self = Self(ffi::CHANNEL_TOP_SIDE_RIGHT);
+    pub const BOTTOM_FRONT_LEFT: Self = Self(ffi::CHANNEL_BOTTOM_FRONT_LEFT);
+    pub const BOTTOM_FRONT_CENTER: Self = Self(ffi::CHANNEL_BOTTOM_FRONT_CENTER);
+    pub const BOTTOM_FRONT_RIGHT: Self = Self(ffi::CHANNEL_BOTTOM_FRONT_RIGHT);
+    pub const LOW_FREQUENCY_2: Self = Self(ffi::CHANNEL_LOW_FREQUENCY_2);
+    pub const FRONT_WIDE_LEFT: Self = Self(ffi::CHANNEL_FRONT_WIDE_LEFT);
+    pub const FRONT_WIDE_RIGHT: Self = Self(ffi::CHANNEL_FRONT_WIDE_RIGHT);
+
+    pub const MONO: Self = Self(ffi::CHANNEL_MONO);
+    pub const STEREO: Self = Self(ffi::CHANNEL_STEREO);
+    /// 2.1
+    pub const TWO_POINT_ONE: Self = Self(ffi::CHANNEL_2POINT1);
+    pub const TRI: Self = Self(ffi::CHANNEL_TRI);
+    pub const TRI_BACK: Self = Self(ffi::CHANNEL_TRI_BACK);
+    /// 3.1
+    pub const THREE_POINT_ONE: Self = Self(ffi::CHANNEL_3POINT1);
+    /// 2.0.2
+    pub const TWO_POINT_ZERO_POINT_TWO: Self = Self(ffi::CHANNEL_2POINT0POINT2);
+    /// 2.1.2
+    pub const TWO_POINT_ONE_POINT_TWO: Self = Self(ffi::CHANNEL_2POINT1POINT2);
+    /// 3.0.2
+    pub const THREE_POINT_ZERO_POINT_TWO: Self = Self(ffi::CHANNEL_3POINT0POINT2);
+    /// 3.1.2
+    pub const THREE_POINT_ONE_POINT_TWO: Self = Self(ffi::CHANNEL_3POINT1POINT2);
+    pub const QUAD: Self = Self(ffi::CHANNEL_QUAD);
+    pub const QUAD_SIDE: Self = Self(ffi::CHANNEL_QUAD_SIDE);
+    pub const SURROUND: Self = Self(ffi::CHANNEL_SURROUND);
+    pub const PENTA: Self = Self(ffi::CHANNEL_PENTA);
+    /// 5.1
+    pub const FIVE_POINT_ONE: Self = Self(ffi::CHANNEL_5POINT1);
+    /// 5.1 with side instead of back channels.
+    pub const FIVE_POINT_ONE_SIDE: Self = Self(ffi::CHANNEL_5POINT1_SIDE);
+    /// 6.1
+    pub const SIX_POINT_ONE: Self = Self(ffi::CHANNEL_6POINT1);
+    /// 7.1
+    pub const SEVEN_POINT_ONE: Self = Self(ffi::CHANNEL_7POINT1);
+    /// 5.1.2
+    pub const FIVE_POINT_ONE_POINT_TWO: Self = Self(ffi::CHANNEL_5POINT1POINT2);
+    /// 5.1.4
+    pub const FIVE_POINT_ONE_POINT_FOUR: Self = Self(ffi::CHANNEL_5POINT1POINT4);
+    /// 7.1.2
+    pub const SEVEN_POINT_ONE_POINT_TWO: Self = Self(ffi::CHANNEL_7POINT1POINT2);
+    /// 7.1.4
+    pub const SEVEN_POINT_ONE_POINT_FOUR: Self = Self(ffi::CHANNEL_7POINT1POINT4);
+    /// 9.1.4
+    pub const NINE_POINT_ONE_POINT_FOUR: Self = Self(ffi::CHANNEL_9POINT1POINT4);
+    /// 9.1.6
+    pub const NINE_POINT_ONE_POINT_SIX: Self = Self(ffi::CHANNEL_9POINT1POINT6);
+    pub const FRONT_BACK: Self = Self(ffi::CHANNEL_FRONT_BACK);
+
+    /// Creates a mask from the raw AAudio value. All bits are retained.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the raw AAudio value of the mask.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns `true` if no channels are set, which is the case for `ChannelMask::UNSPECIFIED`.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all channels of `other` are set in this mask.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the number of channels in the mask, or 0 for `UNSPECIFIED` and `INVALID`.
+    pub const fn channel_count(self) -> i32 {
+        if self.0 == ffi::CHANNEL_INVALID {
+            0
+        } else {
+            self.0.count_ones() as i32
+        }
+    }
+}
+
+impl BitOr for ChannelMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for ChannelMask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for ChannelMask {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for ChannelMask {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0;
+    }
+}
+
+impl From<ChannelMask> for u32 {
+    fn from(mask: ChannelMask) -> u32 {
+        mask.0
+    }
+}
+
+impl From<u32> for ChannelMask {
+    fn from(bits: u32) -> Self {
+        Self(bits)
+    }
+}
+
+impl fmt::Debug for ChannelMask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ChannelMask({:#x})", self.0)
+    }
+}
@@ -0,0 +1,142 @@
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Weak;
+use std::thread;
+
+use crate::handle::StreamInner;
+use crate::{require, Error, ErrorKind, ResultCode};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+thread_local! {
+    /// The id of the worker running on the current thread, or 0.
+    static CURRENT_WORKER: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The worker thread of a stream. It runs the requests of `StreamControl`, the handling
+/// of the errors and panics detected in the callbacks, the restarts, and the closing of
+/// a stream whose last handle is dropped by one of its callbacks.
+///
+/// The thread exits once all clones of the worker are dropped and the pending jobs are run.
+#[derive(Clone)]
+pub(crate) struct Worker {
+    sender: Sender<Job>,
+    id: usize,
+}
+
+impl Worker {
+    pub(crate) fn new() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::channel::<Job>();
+        thread::spawn(move || {
+            CURRENT_WORKER.with(|current| current.set(id));
+            for job in receiver {
+                // The callbacks of the application run by the jobs catch their own panics,
+                // but the worker must keep running for the other jobs in any case.
+                let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            }
+        });
+        Self { sender, id }
+    }
+
+    /// Runs `job` on the worker thread, after the jobs already queued.
+    pub(crate) fn run<F: FnOnce() + Send + 'static>(&self, job: F) {
+        let _ = self.sender.send(Box::new(job));
+    }
+
+    /// Returns `true` if called from the worker thread.
+    pub(crate) fn is_current(&self) -> bool {
+        CURRENT_WORKER.with(Cell::get) == self.id
+    }
+}
+
+/// Passed to the error callback to request operations that must not be performed
+/// from the callback itself, such as stopping, closing or reopening the stream.
+///
+/// The requests are run in order on the worker thread of the stream. That thread also calls
+/// the error callback for the errors and panics detected by the crate, and the requests made
+/// from such a call are run right away, since the queued ones would wait for it to return.
+/// Each request returns a channel that receives the result of the operation once it completes.
+/// The control can be cloned and kept, eg. to restart the stream later.
+#[derive(Clone)]
+pub struct StreamControl {
+    stream: Weak<StreamInner>,
+}
+
+impl StreamControl {
+    pub(crate) fn new(stream: Weak<StreamInner>) -> Self {
+        Self { stream }
+    }
+
+    /// Runs `operation` with the stream on the worker thread and sends its result.
+    fn request<F>(&self, operation: &'static str, body: F) -> Receiver<Result<(), Error>>
+    where
+        F: FnOnce(&StreamInner) -> Result<(), Error> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        match self.stream.upgrade() {
+            Some(inner) if inner.worker().is_current() => {
+                let _ = sender.send(body(&inner));
+            }
+            // The stream is closed on the worker thread if the job holds the last reference.
+            Some(inner) => {
+                let stream = inner.clone();
+                inner.worker().run(move || {
+                    let _ = sender.send(body(&stream));
+                    drop(stream);
+                });
+            }
+            None => {
+                let _ = sender.send(Err(Error::new(
+                    ErrorKind::AAudio(ResultCode::InvalidState),
+                    operation,
+                )));
+            }
+        }
+        receiver
+    }
+
+    /// Requests the stream to stop, see `AAudioStream::request_stop()`.
+    ///
+    /// Fails with `ResultCode::InvalidState` if the stream is already closed.
+    pub fn request_stop(&self) -> Receiver<Result<(), Error>> {
+        self.request("request_stop", |inner| inner.request_stop())
+    }
+
+    /// Requests the stream to stop and to release its audio resources,
+    /// see `AAudioStream::release()`.
+    ///
+    /// The stream itself is closed once the `AAudioStream` and all of its handles are dropped.
+    ///
+    /// Fails with `ErrorKind::FunctionUnavailable` if `AAudioStream::release()` is not
+    /// available, and with `ResultCode::InvalidState` if the stream is already closed.
+    pub fn request_release(&self) -> Receiver<Result<(), Error>> {
+        self.request("request_release", |inner| {
+            let raw = inner.raw();
+            require("AAudioStream_release", "request_release")
+                .map_err(|error| error.with_stream(raw))?;
+            // The resources of a disconnected stream are released even though it cannot be stopped.
+            match inner.request_stop() {
+                Err(error) if error.result_code() == Some(ResultCode::Disconnected) => {}
+                result => result?,
+            }
+            inner.release()
+        })
+    }
+
+    /// Requests the stream to be reopened on the default device with the configuration
+    /// and the callbacks it was opened with, and started.
+    ///
+    /// The `AAudioStream` and its handles then refer to the new stream,
+    /// see `AAudioStreamBuilder::set_auto_restart()`. Fails with `ErrorKind::FormatMismatch`
+    /// or `ErrorKind::ChannelCountMismatch` if the new stream has a different format
+    /// or channel count, and with `ResultCode::InvalidState` if the stream is already closed.
+    pub fn request_restart(&self) -> Receiver<Result<(), Error>> {
+        self.request("request_restart", |inner| {
+            inner.reopen(inner.raw()).map(|_| ())
+        })
+    }
+}
@@ -0,0 +1,359 @@
+use std::convert::TryFrom;
+use std::error;
+use std::ffi::CStr;
+use std::fmt;
+
+use crate::properties::get_basic_config;
+use crate::{
+    ffi, AAudioStreamBuilder, AAudioStreamRaw, Format, StreamConfig, StreamProperties, StreamState,
+    StreamView, UnknownValue,
+};
+
+/// These values are returned from AAudio functions to indicate failure.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ResultCode {
+    /// AAudio returned error code that is not a part of this enum
+    Unknown(i32),
+
+    /// The audio device was disconnected. This could occur, for example, when headphones
+    /// are plugged in or unplugged. The stream cannot be used after the device is disconnected.
+    /// Applications should stop and close the stream.
+    /// If this error is received in an error callback then another thread should be
+    /// used to stop and close the stream.
+    Disconnected,
+
+    /// An invalid parameter was passed to AAudio.
+    IllegalArgument,
+
+    /// An internal error occurred.
+    Internal,
+
+    /// The requested operation is not appropriate for the current state of AAudio.
+    InvalidState,
+
+    /// The server rejected the handle used to identify the stream.
+    InvalidHandle,
+
+    /// The function is not implemented for this stream.
+    Unimplemented,
+
+    /// A resource or information is unavailable.
+    /// This could occur when an application tries to open too many streams,
+    /// or a timestamp is not available.
+    Unavailable,
+
+    /// Memory could not be allocated.
+    NoFreeHandles,
+
+    /// Memory could not be allocated.
+    NoMemory,
+
+    /// A NULL pointer was passed to AAudio.
+    /// Or a NULL pointer was detected internally.
+    Null,
+
+    /// An operation took longer than expected.
+    Timeout,
+
+    WouldBlock,
+
+    /// The requested data format is not supported.
+    InvalidFormat,
+
+    /// A requested was out of range.
+    OutOfRange,
+
+    /// The audio service was not available.
+    NoService,
+
+    /// The requested sample rate was not supported.
+    InvalidRate,
+}
+
+enum_values!(ResultCode {
+    Disconnected = -899,
+    IllegalArgument = -898,
+    Internal = -896,
+    InvalidState = -895,
+    InvalidHandle = -892,
+    Unimplemented = -890,
+    Unavailable = -889,
+    NoFreeHandles = -888,
+    NoMemory = -887,
+    Null = -886,
+    Timeout = -885,
+    WouldBlock = -884,
+    InvalidFormat = -883,
+    OutOfRange = -882,
+    NoService = -881,
+    InvalidRate = -880,
+});
+
+impl fmt::Display for ResultCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unknown(code) => write!(f, "Error code {}", code),
+            Self::Disconnected => f.write_str("The audio device was disconnected"),
+            Self::IllegalArgument => f.write_str("An invalid parameter was passed to AAudio."),
+            Self::Internal => f.write_str("An internal error occurred."),
+            Self::InvalidState => f.write_str(
+                "The requested operation is not appropriate for the current state of AAudio.",
+            ),
+            Self::InvalidHandle => {
+                f.write_str("The server rejected the handle used to identify the stream.")
+            }
+            Self::Unimplemented => f.write_str("The function is not implemented for this stream."),
+            Self::Unavailable => f.write_str("A resource or information is unavailable."),
+            Self::NoFreeHandles => f.write_str("Memory could not be allocated."),
+            Self::NoMemory => f.write_str("Memory could not be allocated"),
+            Self::Null => f.write_str("A NULL pointer was passed to AAudio."),
+            Self::Timeout => f.write_str("An operation took longer than expected."),
+            Self::WouldBlock => {
+                f.write_str("A blocking operation was invoked where no blocking was expected.")
+            }
+            Self::InvalidFormat => f.write_str("The requested data format is not supported."),
+            Self::OutOfRange => f.write_str("A requested was out of range."),
+            Self::NoService => f.write_str("The audio service was not available."),
+            Self::InvalidRate => f.write_str("The requested sample rate was not supported."),
+        }
+    }
+}
+
+/// The cause of an `Error`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An AAudio function returned an error.
+    AAudio(ResultCode),
+
+    /// The stream was opened with a different data format than the one
+    /// required by its data callback or frame type.
+    FormatMismatch { requested: Format, granted: Format },
+
+    /// The stream was opened with a different channel count than the one
+    /// required by its frame type.
+    ChannelCountMismatch { requested: i32, granted: i32 },
+
+    /// The length of a buffer of interleaved samples is not a multiple of the channel count
+    /// of the stream.
+    BufferLength { length: usize, channel_count: i32 },
+
+    /// The buffer passed to a typed data callback is not aligned for its frame type.
+    /// The stream is then stopped instead of calling the callback.
+    MisalignedBuffer { alignment: usize },
+
+    /// The AAudio function with the given name is not available on this device.
+    FunctionUnavailable(&'static str),
+
+    /// A callback panicked, see `PanicPolicy`. The message of the panic is returned
+    /// by `Error::panic_message()`.
+    Panicked,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::AAudio(code) => match result_text(i32::from(*code)) {
+                Some(text) => write!(f, "{}: {}", text, code),
+                None => code.fmt(f),
+            },
+            Self::FormatMismatch { requested, granted } => write!(
+                f,
+                "The stream was opened with {:?} format instead of {:?}.",
+                granted, requested
+            ),
+            Self::ChannelCountMismatch { requested, granted } => write!(
+                f,
+                "The stream was opened with {} channels instead of {}.",
+                granted, requested
+            ),
+            Self::BufferLength {
+                length,
+                channel_count,
+            } => write!(
+                f,
+                "The buffer of {} samples does not contain whole frames of {} channels.",
+                length, channel_count
+            ),
+            Self::MisalignedBuffer { alignment } => write!(
+                f,
+                "The buffer of the data callback is not aligned to {} bytes.",
+                alignment
+            ),
+            Self::FunctionUnavailable(function) => {
+                write!(f, "{} is not available on this device.", function)
+            }
+            Self::Panicked => f.write_str("The callback panicked."),
+        }
+    }
+}
+
+/// Returns the symbolic name of an AAudio result code, eg. `AAUDIO_ERROR_DISCONNECTED`.
+fn result_text(code: i32) -> Option<&'static str> {
+    if !ffi::is_available("AAudio_convertResultToText") {
+        return None;
+    }
+    let text = unsafe { ffi::AAudio_convertResultToText(code) };
+    if text.is_null() {
+        return None;
+    }
+    unsafe { CStr::from_ptr(text) }.to_str().ok()
+}
+
+/// An error returned from an operation on a stream or a builder.
+///
+/// Besides its cause, the error records the failed operation and, if the error is related to
+/// an open stream, the state and the configuration of the stream at the time of failure.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Error {
+    kind: ErrorKind,
+    operation: &'static str,
+    state: Option<StreamState>,
+    config: Option<Box<StreamConfig>>,
+    panic_message: Option<String>,
+}
+
+impl Error {
+    pub(crate) fn new(kind: ErrorKind, operation: &'static str) -> Self {
+        Self {
+            kind,
+            operation,
+            state: None,
+            config: None,
+            panic_message: None,
+        }
+    }
+
+    pub(crate) fn from_code(code: i32, operation: &'static str) -> Self {
+        Self::new(ErrorKind::AAudio(ResultCode::from_i32(code)), operation)
+    }
+
+    /// Records the state and the configuration of the stream.
+    ///
+    /// Only the values available since API level 26 are recorded, see `get_basic_config()`.
+    pub(crate) fn with_stream(mut self, raw: *mut AAudioStreamRaw) -> Self {
+        let stream = unsafe { StreamView::new(raw) };
+        self.state = Some(stream.get_state());
+        self.config = Some(Box::new(get_basic_config(&stream)));
+        self
+    }
+
+    pub(crate) fn with_panic_message(mut self, message: String) -> Self {
+        self.panic_message = Some(message);
+        self
+    }
+
+    /// Returns the cause of the error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the AAudio result code, if the error was returned by AAudio.
+    pub fn result_code(&self) -> Option<ResultCode> {
+        match self.kind {
+            ErrorKind::AAudio(code) => Some(code),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the failed operation, eg. `"request_start"`.
+    pub fn operation(&self) -> &'static str {
+        self.operation
+    }
+
+    /// Returns the state of the stream at the time of failure,
+    /// if the error is related to an open stream.
+    pub fn state(&self) -> Option<StreamState> {
+        self.state
+    }
+
+    /// Returns the configuration of the stream at the time of failure,
+    /// if the error is related to an open stream.
+    ///
+    /// The values added after API level 26 are not recorded and are `None`.
+    pub fn config(&self) -> Option<&StreamConfig> {
+        self.config.as_deref()
+    }
+
+    /// Returns the message of the panic, if the error is `ErrorKind::Panicked`
+    /// and the panic payload is a string.
+    pub fn panic_message(&self) -> Option<&str> {
+        self.panic_message.as_deref()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} failed: {}", self.operation, self.kind)?;
+        if let Some(message) = &self.panic_message {
+            write!(f, " {}", message)?;
+        }
+        if let Some(state) = self.state {
+            write!(f, " (stream state: {:?})", state)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Error {}
+
+/// An error returned from a setter of `AAudioStreamBuilder`, eg. when the AAudio function is
+/// not available on this device.
+///
+/// The error owns the builder, so that the application can fall back to other options
+/// and keep building the stream.
+pub struct BuilderError {
+    builder: Box<AAudioStreamBuilder>,
+    error: Error,
+}
+
+impl BuilderError {
+    pub(crate) fn new(builder: AAudioStreamBuilder, error: Error) -> Self {
+        Self {
+            builder: Box::new(builder),
+            error,
+        }
+    }
+
+    /// Returns the cause of the failure.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Returns the builder, unchanged by the failed setter.
+    pub fn into_builder(self) -> AAudioStreamBuilder {
+        *self.builder
+    }
+
+    /// Returns both the builder and the cause of the failure.
+    pub fn into_parts(self) -> (AAudioStreamBuilder, Error) {
+        (*self.builder, self.error)
+    }
+}
+
+impl fmt::Debug for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BuilderError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl error::Error for BuilderError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<BuilderError> for Error {
+    fn from(error: BuilderError) -> Self {
+        error.error
+    }
+}
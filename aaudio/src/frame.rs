@@ -0,0 +1,75 @@
+use crate::Sample;
+
+mod private {
+    pub trait Sealed {}
+
+    impl<T: crate::Sample> Sealed for T {}
+    impl<T: crate::Sample> Sealed for super::Mono<T> {}
+    impl<T: crate::Sample> Sealed for super::Stereo<T> {}
+    impl<T: crate::Sample, const N: usize> Sealed for super::Frame<T, N> {}
+}
+
+/// A type of the elements of typed audio buffers.
+///
+/// Every [`Sample`] type is an `AudioFrame` on its own: buffers of samples are interleaved
+/// and can be used with any channel count. [`Mono`], [`Stereo`] and [`Frame`] contain
+/// one sample for each channel and require the stream to have that many channels.
+///
+/// This trait is sealed.
+pub trait AudioFrame: private::Sealed + Copy + Send + 'static {
+    /// The type of the samples of the frame.
+    type Sample: Sample;
+
+    /// The number of channels stored in a single value, or `None` for `Sample` types.
+    const CHANNEL_COUNT: Option<i32>;
+}
+
+impl<T: Sample> AudioFrame for T {
+    type Sample = T;
+    const CHANNEL_COUNT: Option<i32> = None;
+}
+
+/// A frame of a single channel stream.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Mono<T>(pub T);
+
+impl<T: Sample> AudioFrame for Mono<T> {
+    type Sample = T;
+    const CHANNEL_COUNT: Option<i32> = Some(1);
+}
+
+/// A frame of a two channel stream.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Stereo<T> {
+    pub left: T,
+    pub right: T,
+}
+
+impl<T: Sample> AudioFrame for Stereo<T> {
+    type Sample = T;
+    const CHANNEL_COUNT: Option<i32> = Some(2);
+}
+
+/// A frame of a stream with `N` channels.
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frame<T, const N: usize>(pub [T; N]);
+
+impl<T: Sample, const N: usize> AudioFrame for Frame<T, N> {
+    type Sample = T;
+    const CHANNEL_COUNT: Option<i32> = Some(N as i32);
+}
+
+impl<T: Sample, const N: usize> Default for Frame<T, N> {
+    fn default() -> Self {
+        Frame([T::default(); N])
+    }
+}
+
+impl<T, const N: usize> From<[T; N]> for Frame<T, N> {
+    fn from(samples: [T; N]) -> Self {
+        Frame(samples)
+    }
+}
@@ -0,0 +1,434 @@
+use std::any::Any;
+use std::ffi::CString;
+use std::mem::{self, MaybeUninit};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use std::time::{Duration, Instant};
+
+use crate::callback::CallbackObject;
+use crate::control::Worker;
+use crate::panic::{self, PanicHandler};
+use crate::properties::private::Sealed;
+use crate::restart::Restart;
+use crate::{
+    ffi, require, wrap_stream_result, AAudioStreamBuilderRaw, AAudioStreamRaw, Error, ErrorHandler,
+    ErrorKind, ResultCode, StreamCallbacks, StreamProperties, StreamState, StreamView,
+};
+
+/// How long a restart or the handling of an error waits for a stream to stop.
+const STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// An open AAudio stream together with the callbacks registered for it,
+/// shared by an `AAudioStream` and its handles.
+pub(crate) struct StreamInner {
+    /// The current stream, replaced when the stream is restarted after a disconnect.
+    raw: AtomicPtr<AAudioStreamRaw>,
+    /// The stream replaced by the last restart. It is stopped, but only closed on the next
+    /// restart or together with the current stream, since other threads may still be
+    /// querying it. Locked for the whole restart, so that only one restart runs at a time.
+    retired: Mutex<Option<*mut AAudioStreamRaw>>,
+    /// The builder the stream was opened with, used to reopen it.
+    builder: *mut AAudioStreamBuilderRaw,
+    /// The strings passed to `builder`, which must stay valid while it is used.
+    strings: Vec<CString>,
+    /// Held while waiting for a state change, reading or writing. AAudio does not allow
+    /// these calls to run concurrently on the same stream.
+    io: Mutex<()>,
+    callbacks: StreamCallbacks,
+    restart: Option<Box<Restart>>,
+    /// Runs the requests of `StreamControl` and the jobs that must not run in a callback.
+    worker: Worker,
+}
+
+// AAudio streams can be queried from any thread, and the callbacks are only
+// invoked by AAudio. The calls that must not run concurrently are serialized by `io`.
+unsafe impl Send for StreamInner {}
+unsafe impl Sync for StreamInner {}
+
+impl StreamInner {
+    /// Takes the ownership of `raw`, of the `builder` it was opened with and of the `strings`
+    /// passed to the builder.
+    pub(crate) fn new(
+        raw: *mut AAudioStreamRaw,
+        builder: *mut AAudioStreamBuilderRaw,
+        strings: Vec<CString>,
+        callbacks: StreamCallbacks,
+        restart: Option<Box<Restart>>,
+    ) -> Arc<Self> {
+        let inner = Arc::new(Self {
+            raw: AtomicPtr::new(raw),
+            retired: Mutex::new(None),
+            builder,
+            strings,
+            io: Mutex::new(()),
+            callbacks,
+            restart,
+            worker: Worker::new(),
+        });
+        inner
+            .callbacks
+            .panic
+            .attach(Arc::downgrade(&inner), inner.worker.clone());
+        if let Some(handler) = inner.error_handler() {
+            handler.attach(Arc::downgrade(&inner));
+        }
+        if let Some(object) = inner.callback_object() {
+            object.attach(Arc::downgrade(&inner));
+        }
+        if let Some(restart) = &inner.restart {
+            restart.attach(Arc::downgrade(&inner));
+        }
+        inner
+    }
+
+    pub(crate) fn raw(&self) -> *mut AAudioStreamRaw {
+        self.raw.load(Ordering::Acquire)
+    }
+
+    /// Reopens the stream with its builder on the default device and starts it,
+    /// releasing the audio resources of the `previous` stream.
+    ///
+    /// Returns the new stream, or `None` if `previous` was already replaced by another restart.
+    /// Fails if `previous` does not stop within `STOP_TIMEOUT`, if the stream cannot be opened
+    /// or started, or is opened with a different format or channel count.
+    pub(crate) fn reopen(
+        &self,
+        previous: *mut AAudioStreamRaw,
+    ) -> Result<Option<*mut AAudioStreamRaw>, Error> {
+        let mut retired = self.retired.lock().unwrap_or_else(PoisonError::into_inner);
+        if self.raw() != previous {
+            return Ok(None);
+        }
+        let _ = unsafe { ffi::AAudioStream_requestStop(previous) };
+        // Both streams share the callbacks, which must not run concurrently.
+        self.wait_until_stopped(previous, "restart")?;
+        let mut raw = MaybeUninit::<*mut AAudioStreamRaw>::uninit();
+        let result = unsafe {
+            ffi::AAudioStreamBuilder_setDeviceId(self.builder, ffi::UNSPECIFIED);
+            ffi::AAudioStreamBuilder_openStream(self.builder, raw.as_mut_ptr())
+        };
+        if result < 0 {
+            return Err(Error::from_code(result, "restart").with_stream(previous));
+        }
+        let raw = unsafe { raw.assume_init() };
+        let (stream, old) = unsafe { (StreamView::new(raw), StreamView::new(previous)) };
+        let kind = if stream.get_format() != old.get_format() {
+            Some(ErrorKind::FormatMismatch {
+                requested: old.get_format(),
+                granted: stream.get_format(),
+            })
+        } else if stream.get_channel_count() != old.get_channel_count() {
+            Some(ErrorKind::ChannelCountMismatch {
+                requested: old.get_channel_count(),
+                granted: stream.get_channel_count(),
+            })
+        } else {
+            None
+        };
+        if let Some(kind) = kind {
+            unsafe { ffi::AAudioStream_close(raw) };
+            return Err(Error::new(kind, "restart").with_stream(previous));
+        }
+        self.raw.store(raw, Ordering::Release);
+        if ffi::is_available("AAudioStream_release") {
+            unsafe { ffi::AAudioStream_release(previous) };
+        }
+        // The stream replaced by the last restart has been stopped for a while.
+        if let Some(retired) = retired.replace(previous) {
+            unsafe { ffi::AAudioStream_close(retired) };
+        }
+        let result = unsafe { ffi::AAudioStream_requestStart(raw) };
+        wrap_stream_result(raw, result, "restart")?;
+        Ok(Some(raw))
+    }
+
+    /// Waits for `raw`, the current stream, to be stopped or disconnected,
+    /// after which its callbacks are no longer running.
+    ///
+    /// Fails with `ResultCode::Timeout` if it takes longer than `STOP_TIMEOUT`.
+    pub(crate) fn wait_until_stopped(
+        &self,
+        raw: *mut AAudioStreamRaw,
+        operation: &'static str,
+    ) -> Result<(), Error> {
+        let _io = self.lock_io();
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        loop {
+            let state = unsafe { ffi::AAudioStream_getState(raw) };
+            match StreamState::from_i32(state) {
+                StreamState::Stopped
+                | StreamState::Disconnected
+                | StreamState::Closing
+                | StreamState::Closed => return Ok(()),
+                _ => {}
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Err(
+                    Error::new(ErrorKind::AAudio(ResultCode::Timeout), operation).with_stream(raw),
+                );
+            }
+            let mut new_state = MaybeUninit::uninit();
+            let result = unsafe {
+                ffi::AAudioStream_waitForStateChange(
+                    raw,
+                    state,
+                    new_state.as_mut_ptr(),
+                    timeout.as_nanos() as i64,
+                )
+            };
+            wrap_stream_result(raw, result, operation)?;
+        }
+    }
+
+    pub(crate) fn worker(&self) -> &Worker {
+        &self.worker
+    }
+
+    /// Returns the auto restart state of the stream, if enabled.
+    pub(crate) fn restart(&self) -> Option<&Restart> {
+        self.restart.as_deref()
+    }
+
+    /// Returns the error callback registered for the stream, if any.
+    pub(crate) fn error_handler(&self) -> Option<&ErrorHandler> {
+        self.callbacks._error_callback.as_deref()
+    }
+
+    pub(crate) fn panic_handler(&self) -> &PanicHandler {
+        &self.callbacks.panic
+    }
+
+    /// Returns the `AudioStreamCallback` registered for the stream, if any.
+    pub(crate) fn callback_object(&self) -> Option<&CallbackObject> {
+        self.callbacks._callback.as_deref()
+    }
+
+    /// Locks the stream for a call that must not run concurrently with other
+    /// reads, writes or waits for a state change.
+    pub(crate) fn lock_io(&self) -> MutexGuard<'_, ()> {
+        self.io.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn release(&self) -> Result<(), Error> {
+        let raw = self.raw();
+        require("AAudioStream_release", "release").map_err(|error| error.with_stream(raw))?;
+        let val = unsafe { ffi::AAudioStream_release(raw) };
+        wrap_stream_result(raw, val, "release")
+    }
+
+    pub(crate) fn request_start(&self) -> Result<(), Error> {
+        let raw = self.raw();
+        let val = unsafe { ffi::AAudioStream_requestStart(raw) };
+        wrap_stream_result(raw, val, "request_start")
+    }
+
+    pub(crate) fn request_pause(&self) -> Result<(), Error> {
+        let raw = self.raw();
+        let val = unsafe { ffi::AAudioStream_requestPause(raw) };
+        wrap_stream_result(raw, val, "request_pause")
+    }
+
+    pub(crate) fn request_flush(&self) -> Result<(), Error> {
+        let raw = self.raw();
+        let val = unsafe { ffi::AAudioStream_requestFlush(raw) };
+        wrap_stream_result(raw, val, "request_flush")
+    }
+
+    pub(crate) fn request_stop(&self) -> Result<(), Error> {
+        let raw = self.raw();
+        let val = unsafe { ffi::AAudioStream_requestStop(raw) };
+        wrap_stream_result(raw, val, "request_stop")
+    }
+
+    pub(crate) fn wait_for_state_change(
+        &self,
+        input_state: StreamState,
+        timeout_nanos: i64,
+    ) -> Result<StreamState, Error> {
+        let _io = self.lock_io();
+        let raw = self.raw();
+        let mut new_state = MaybeUninit::uninit();
+        let result = unsafe {
+            ffi::AAudioStream_waitForStateChange(
+                raw,
+                input_state.into(),
+                new_state.as_mut_ptr(),
+                timeout_nanos,
+            )
+        };
+        wrap_stream_result(raw, result, "wait_for_state_change")?;
+        Ok(StreamState::from_i32(unsafe { new_state.assume_init() }))
+    }
+
+    pub(crate) fn set_buffer_size_in_frames(&self, num_frames: i32) -> Result<(), Error> {
+        let raw = self.raw();
+        let result = unsafe { ffi::AAudioStream_setBufferSizeInFrames(raw, num_frames) };
+        wrap_stream_result(raw, result, "set_buffer_size_in_frames")
+    }
+}
+
+impl Drop for StreamInner {
+    fn drop(&mut self) {
+        let closing = Closing {
+            raw: self.raw(),
+            retired: self
+                .retired
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take(),
+            builder: self.builder,
+            _strings: mem::take(&mut self.strings),
+            _callbacks: mem::take(&mut self.callbacks),
+            _restart: self.restart.take(),
+        };
+        // The last handle of a stream may be dropped by one of its callbacks. Closing the stream
+        // waits for the callbacks to return, and the running callback must not be dropped.
+        if panic::in_callback() {
+            self.worker.run(move || drop(closing));
+        }
+    }
+}
+
+/// The resources of a dropped stream. The streams are closed before the callbacks
+/// they point to are dropped.
+struct Closing {
+    raw: *mut AAudioStreamRaw,
+    retired: Option<*mut AAudioStreamRaw>,
+    builder: *mut AAudioStreamBuilderRaw,
+    _strings: Vec<CString>,
+    _callbacks: StreamCallbacks,
+    _restart: Option<Box<Restart>>,
+}
+
+// Only moved to the worker thread to be dropped.
+unsafe impl Send for Closing {}
+
+impl Drop for Closing {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::AAudioStream_close(self.raw);
+            if let Some(retired) = self.retired {
+                ffi::AAudioStream_close(retired);
+            }
+            ffi::AAudioStreamBuilder_delete(self.builder);
+        }
+    }
+}
+
+/// A reference-counted handle to an open stream, returned from `AAudioStream::handle()`.
+///
+/// Unlike the `StreamView` passed to callbacks, a handle can be cloned, stored and sent
+/// to other threads to query the stream with the getters of `StreamProperties` and to
+/// control it. The control methods take `&self`, so a handle can be shared by several
+/// threads, eg. in an `Arc`. Waiting for a state change is serialized with the reads
+/// and writes of the stream, as required by AAudio; the other requests are not blocked.
+///
+/// The stream is closed once the `AAudioStream` and all of its handles are dropped.
+/// A handle stored in the callbacks of its own stream would keep it open forever,
+/// so store a `WeakStreamHandle` there instead.
+#[derive(Clone)]
+pub struct StreamHandle {
+    inner: Arc<StreamInner>,
+}
+
+impl StreamHandle {
+    pub(crate) fn new(inner: Arc<StreamInner>) -> Self {
+        Self { inner }
+    }
+
+    /// Returns a weak handle to the stream, which does not keep the stream open.
+    pub fn downgrade(&self) -> WeakStreamHandle {
+        WeakStreamHandle {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// See `AAudioStream::take_panic()`.
+    pub fn take_panic(&self) -> Option<Box<dyn Any + Send>> {
+        self.inner.panic_handler().take_unreported()
+    }
+
+    /// See `AAudioStream::request_start()`.
+    ///
+    /// Available since API level 26.
+    pub fn request_start(&self) -> Result<(), Error> {
+        self.inner.request_start()
+    }
+
+    /// See `AAudioStream::request_pause()`.
+    ///
+    /// Available since API level 26.
+    pub fn request_pause(&self) -> Result<(), Error> {
+        self.inner.request_pause()
+    }
+
+    /// See `AAudioStream::request_flush()`.
+    ///
+    /// Available since API level 26.
+    pub fn request_flush(&self) -> Result<(), Error> {
+        self.inner.request_flush()
+    }
+
+    /// See `AAudioStream::request_stop()`.
+    ///
+    /// Available since API level 26.
+    pub fn request_stop(&self) -> Result<(), Error> {
+        self.inner.request_stop()
+    }
+
+    /// See `AAudioStream::release()`.
+    ///
+    /// Available since API level 30.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    pub fn release(&self) -> Result<(), Error> {
+        self.inner.release()
+    }
+
+    /// See `AAudioStream::wait_for_state_change()`.
+    ///
+    /// Blocks the reads and writes of the stream until the call returns.
+    ///
+    /// Available since API level 26.
+    pub fn wait_for_state_change(
+        &self,
+        input_state: StreamState,
+        timeout_nanos: i64,
+    ) -> Result<StreamState, Error> {
+        self.inner.wait_for_state_change(input_state, timeout_nanos)
+    }
+
+    /// See `AAudioStream::set_buffer_size_in_frames()`.
+    ///
+    /// Available since API level 26.
+    pub fn set_buffer_size_in_frames(&self, num_frames: i32) -> Result<(), Error> {
+        self.inner.set_buffer_size_in_frames(num_frames)
+    }
+}
+
+impl Sealed for StreamHandle {
+    fn raw(&self) -> *mut AAudioStreamRaw {
+        self.inner.raw()
+    }
+}
+
+impl StreamProperties for StreamHandle {}
+
+/// A weak reference to an open stream, returned from `StreamHandle::downgrade()`.
+///
+/// Unlike a `StreamHandle`, it does not keep the stream open, so it can be stored
+/// in the callbacks of its own stream.
+#[derive(Clone)]
+pub struct WeakStreamHandle {
+    inner: Weak<StreamInner>,
+}
+
+impl WeakStreamHandle {
+    /// Returns a handle to the stream, or `None` if the stream was closed.
+    ///
+    /// If the returned handle is the last one, the stream is closed when it is dropped,
+    /// on another thread if it is dropped by a callback of the stream.
+    pub fn upgrade(&self) -> Option<StreamHandle> {
+        self.inner.upgrade().map(StreamHandle::new)
+    }
+}
@@ -1,146 +1,132 @@
 extern crate libc;
 extern crate aaudio_sys;
 
-use std::ffi::c_void;
+use std::any::Any;
+use std::convert::TryFrom;
+use std::ffi::{c_void, CString};
 use std::fmt;
-use std::mem::MaybeUninit;
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, Weak};
+use std::time::Duration;
 
 use aaudio_sys as ffi;
 use ffi::{AAudioStream as AAudioStreamRaw, AAudioStreamBuilder as AAudioStreamBuilderRaw};
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
-/// These values are returned from AAudio functions to indicate failure.
-pub enum Error {
-    /// AAudio returned error code that is not a part of this enum
-    Unknown(i32),
-
-    /// The audio device was disconnected. This could occur, for example, when headphones
-    /// are plugged in or unplugged. The stream cannot be used after the device is disconnected.
-    /// Applications should stop and close the stream.
-    /// If this error is received in an error callback then another thread should be
-    /// used to stop and close the stream.
-    Disconnected,
-
-    /// An invalid parameter was passed to AAudio.
-    IllegalArgument,
-
-    /// The requested operation is not appropriate for the current state of AAudio.
-    InvalidState,
-
-    /// The server rejected the handle used to identify the stream.
-    InvalidHandle,
+#[cfg(feature = "mock")]
+pub use ffi::mock;
 
-    /// The function is not implemented for this stream.
-    Unimplemented,
+#[cfg(all(feature = "dynamic", not(feature = "mock")))]
+pub use ffi::dynamic;
 
-    /// A resource or information is unavailable.
-    /// This could occur when an application tries to open too many streams,
-    /// or a timestamp is not available.
-    Unavailable,
+pub use ffi::is_available;
 
-    /// Memory could not be allocated.
-    NoFreeHandles,
-
-    /// Memory could not be allocated.
-    NoMemory,
-
-    /// A NULL pointer was passed to AAudio.
-    /// Or a NULL pointer was detected internally.
-    Null,
-
-    /// An operation took longer than expected.
-    Timeout,
+/// Error returned when converting a raw AAudio value that does not match any variant
+/// of an enum.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct UnknownValue(pub i32);
 
-    WouldBlock,
+impl fmt::Display for UnknownValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unknown AAudio value {}", self.0)
+    }
+}
 
-    /// The requested data format is not supported.
-    InvalidFormat,
+impl std::error::Error for UnknownValue {}
 
-    /// A requested was out of range.
-    OutOfRange,
+/// Implements the conversions of an enum from and to the raw AAudio values.
+///
+/// Values that do not match any variant are stored in the `Unknown` variant,
+/// or in the variant named after `else`.
+macro_rules! enum_values {
+    ($name:ident { $($variant:ident = $value:literal,)* }) => {
+        enum_values!($name { $($variant = $value,)* } else Unknown);
+    };
+    ($name:ident { $($variant:ident = $value:literal,)* } else $other:ident) => {
+        impl $name {
+            fn from_i32(val: i32) -> Self {
+                match val {
+                    $($value => Self::$variant,)*
+                    val => Self::$other(val),
+                }
+            }
+        }
 
-    /// The audio service was not available.
-    NoService,
+        impl From<$name> for i32 {
+            fn from(val: $name) -> i32 {
+                match val {
+                    $($name::$variant => $value,)*
+                    $name::$other(val) => val,
+                }
+            }
+        }
 
-    /// The requested sample rate was not supported.
-    InvalidRate,
-}
+        impl TryFrom<i32> for $name {
+            type Error = UnknownValue;
 
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::Unknown(code) => write!(f, "Error code {}", code),
-            Self::Disconnected => f.write_str("The audio device was disconnected"),
-            Self::IllegalArgument => f.write_str("An invalid parameter was passed to AAudio."),
-            Self::InvalidState => f.write_str(
-                "The requested operation is not appropriate for the current state of AAudio.",
-            ),
-            Self::InvalidHandle => {
-                f.write_str("The server rejected the handle used to identify the stream.")
-            }
-            Self::Unimplemented => f.write_str("The function is not implemented for this stream."),
-            Self::Unavailable => f.write_str("A resource or information is unavailable."),
-            Self::NoFreeHandles => f.write_str("Memory could not be allocated."),
-            Self::NoMemory => f.write_str("Memory could not be allocated"),
-            Self::Null => f.write_str("A NULL pointer was passed to AAudio."),
-            Self::Timeout => f.write_str("An operation took longer than expected."),
-            Self::WouldBlock => {
-                f.write_str("A blocking operation was invoked where no blocking was expected.")
+            fn try_from(val: i32) -> Result<Self, UnknownValue> {
+                match Self::from_i32(val) {
+                    $name::$other(val) => Err(UnknownValue(val)),
+                    val => Ok(val),
+                }
             }
-            Self::InvalidFormat => f.write_str("The requested data format is not supported."),
-            Self::OutOfRange => f.write_str("A requested was out of range."),
-            Self::NoService => f.write_str("The audio service was not available."),
-            Self::InvalidRate => f.write_str("The requested sample rate was not supported."),
         }
-    }
+    };
 }
 
-impl Error {
-    fn from_code(code: i32) -> Self {
-        match code {
-            -899 => Self::Disconnected,
-            -898 => Self::IllegalArgument,
-            -895 => Self::InvalidState,
-            -892 => Self::InvalidHandle,
-            -890 => Self::Unimplemented,
-            -889 => Self::Unavailable,
-            -888 => Self::NoFreeHandles,
-            -887 => Self::NoMemory,
-            -886 => Self::Null,
-            -885 => Self::Timeout,
-            -884 => Self::WouldBlock,
-            -883 => Self::InvalidFormat,
-            -882 => Self::OutOfRange,
-            -881 => Self::NoService,
-            -880 => Self::InvalidRate,
-            code => Self::Unknown(code),
-        }
-    }
-}
+mod callback;
+mod channel_mask;
+mod control;
+mod error;
+mod frame;
+mod handle;
+mod panic;
+mod properties;
+mod restart;
+mod ring_buffer;
+mod sample;
+mod stream;
+
+pub use callback::AudioStreamCallback;
+use callback::{raw_callback_data, raw_callback_error, CallbackObject};
+pub use channel_mask::ChannelMask;
+pub use control::StreamControl;
+pub use error::{BuilderError, Error, ErrorKind, ResultCode};
+pub use frame::{AudioFrame, Frame, Mono, Stereo};
+use handle::StreamInner;
+pub use handle::{StreamHandle, WeakStreamHandle};
+pub use panic::PanicPolicy;
+use panic::{PanicHandler, PanicHandlerRef};
+use properties::private::Sealed;
+pub use properties::StreamProperties;
+use restart::{raw_restart_error_callback, Restart};
+pub use ring_buffer::{ring_buffer, RingBufferConsumer, RingBufferProducer};
+pub use sample::{Sample, I24};
+pub use stream::{InputStream, OutputStream};
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum Direction {
     /// Audio data will travel out of the device, for example through a speaker.
     Output,
 
     /// Audio data will travel into the device, for example from a microphone.
     Input,
-}
 
-impl Direction {
-    fn from_i32(val: i32) -> Self {
-        match val {
-            0 => Self::Output,
-            1 => Self::Input,
-            direction => panic!("Unexpected direction: {}", direction),
-        }
-    }
+    /// A value that is not known to this version of the crate.
+    Unknown(i32),
 }
 
+enum_values!(Direction {
+    Output = 0,
+    Input = 1,
+});
+
 /// A sample format.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum Format {
-    Unspecified = 0,
+    Unspecified,
 
     /// This format uses the i16 data type.
     /// The maximum range of the data is -32768 to 32767.
@@ -153,28 +139,135 @@ pub enum Format {
     /// See also 'floatData' at
     /// https://developer.android.com/reference/android/media/AudioTrack#write(float[],%20int,%20int,%20int)
     F32,
+
+    /// This format uses 24-bit samples packed into 3 bytes.
+    /// The bytes are in little-endian order, so the least significant byte
+    /// comes first in the byte array.
+    ///
+    /// The maximum range of the data is -8388608 (0x800000)
+    /// to 8388607 (0x7FFFFF).
+    ///
+    /// Note that the lower precision bits may be ignored by the device.
+    ///
+    /// Available since API level 31.
+    I24Packed,
+
+    /// This format uses 32-bit samples stored in an i32 variable.
+    /// The maximum range of the data is -2,147,483,648 (0x80000000)
+    /// to 2,147,483,647 (0x7FFFFFFF).
+    ///
+    /// Note that the lower precision bits may be ignored by the device.
+    ///
+    /// Available since API level 31.
+    I32,
+
+    /// This format is used for compressed audio wrapped in IEC61937 for HDMI
+    /// or S/PDIF passthrough. The data is transferred in 16-bit words.
+    ///
+    /// Unlike PCM playback, the Android framework is not able to do format
+    /// conversion for IEC61937. In that case, when IEC61937 is requested, sampling
+    /// rate and channel count or channel mask must be specified. Otherwise, it may
+    /// fail when opening the stream. Apps are able to get the correct configuration
+    /// for the playback by calling AudioManager#getDevices(int).
+    ///
+    /// Available since API level 34.
+    Iec61937,
+
+    /// This format is used for audio compressed in MP3 format.
+    ///
+    /// Compressed formats are only supported by output streams with
+    /// `PerformanceMode::PowerSavingOffloaded`. The data is transferred in bytes,
+    /// so a frame of a compressed format is a single byte.
+    ///
+    /// Available since API level 36.
+    Mp3,
+
+    /// This format is used for audio compressed in AAC LC format.
+    ///
+    /// Available since API level 36.
+    AacLc,
+
+    /// This format is used for audio compressed in AAC HE V1 format.
+    ///
+    /// Available since API level 36.
+    AacHeV1,
+
+    /// This format is used for audio compressed in AAC HE V2 format.
+    ///
+    /// Available since API level 36.
+    AacHeV2,
+
+    /// This format is used for audio compressed in AAC ELD format.
+    ///
+    /// Available since API level 36.
+    AacEld,
+
+    /// This format is used for audio compressed in AAC XHE format.
+    ///
+    /// Available since API level 36.
+    AacXhe,
+
+    /// This format is used for audio compressed in OPUS format.
+    ///
+    /// Available since API level 36.
+    Opus,
+
+    /// A value that is not known to this version of the crate.
+    Unknown(i32),
 }
 
+enum_values!(Format {
+    Unspecified = 0,
+    I16 = 1,
+    F32 = 2,
+    I24Packed = 3,
+    I32 = 4,
+    Iec61937 = 5,
+    Mp3 = 6,
+    AacLc = 7,
+    AacHeV1 = 8,
+    AacHeV2 = 9,
+    AacEld = 10,
+    AacXhe = 11,
+    Opus = 12,
+});
+
 impl Format {
-    fn from_i32(val: i32) -> Self {
-        match val {
-            0 => Self::Unspecified,
-            1 => Self::I16,
-            2 => Self::F32,
-            format => panic!("Unexpected format: {}", format),
-        }
+    /// Returns `true` for the compressed formats used by offloaded playback, eg. `Format::Mp3`.
+    pub fn is_compressed(&self) -> bool {
+        matches!(
+            self,
+            Self::Mp3
+                | Self::AacLc
+                | Self::AacHeV1
+                | Self::AacHeV2
+                | Self::AacEld
+                | Self::AacXhe
+                | Self::Opus
+        )
     }
 
     fn sample_size(&self) -> i32 {
         match self {
-            Self::Unspecified => 0,
-            Self::I16 => 2,
-            Self::F32 => 4,
+            Self::I16 | Self::Iec61937 => 2,
+            Self::I24Packed => 3,
+            Self::F32 | Self::I32 => 4,
+            _ => 0,
+        }
+    }
+
+    /// Returns the size of a frame in bytes. A frame of compressed data is a single byte.
+    fn frame_size(&self, channel_count: i32) -> i32 {
+        if self.is_compressed() {
+            1
+        } else {
+            channel_count.saturating_mul(self.sample_size())
         }
     }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum SharingMode {
     /// This will be the only stream using a particular source or sink.
     /// This mode will provide the lowest possible latency.
@@ -183,18 +276,16 @@ pub enum SharingMode {
     /// Multiple applications will be mixed by the AAudio Server.
     /// This will have higher latency than the Exclusive mode.
     Shared,
-}
 
-impl SharingMode {
-    fn from_i32(val: i32) -> Self {
-        match val {
-            0 => Self::Exclusive,
-            1 => Self::Shared,
-            mode => panic!("Unexpected sharing mode: {}", mode),
-        }
-    }
+    /// A value that is not known to this version of the crate.
+    Unknown(i32),
 }
 
+enum_values!(SharingMode {
+    Exclusive = 0,
+    Shared = 1,
+});
+
 /// The Usage attribute expresses "why" you are playing a sound, what is this sound used for.
 /// This information is used by certain platforms or routing policies
 /// to make more refined volume or routing decisions.
@@ -204,61 +295,84 @@ impl SharingMode {
 ///
 /// Added in API level 28.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum Usage {
     /// Use this for streaming media, music performance, video, podcasts, etcetera.
-    Media = 1,
+    Media,
 
     /// Use this for voice over IP, telephony, etcetera.
-    VoiceCommunication = 2,
+    VoiceCommunication,
 
     /// Use this for sounds associated with telephony such as busy tones, DTMF, etcetera.
-    VoiceCommunicationSignalling = 3,
+    VoiceCommunicationSignalling,
 
     /// Use this to demand the users attention.
-    Alarm = 4,
+    Alarm,
 
     /// Use this for notifying the user when a message has arrived or some
     /// other background event has occured.
-    Notification = 5,
+    Notification,
 
     /// Use this when the phone rings.
-    NotificationRingtone = 6,
+    NotificationRingtone,
 
     /// Use this to attract the users attention when, for example, the battery is low.
-    NotificationEvent = 10,
+    NotificationEvent,
 
     /// Use this for screen readers, etcetera.
-    AssistanceAccessibility = 11,
+    AssistanceAccessibility,
 
     /// Use this for driving or navigation directions.
-    AssistanceNavigationGuidance = 12,
+    AssistanceNavigationGuidance,
 
     /// Use this for user interface sounds, beeps, etcetera.
-    AssistanceSonification = 13,
+    AssistanceSonification,
 
     /// Use this for game audio and sound effects.
-    Game = 14,
+    Game,
 
     /// Use this for audio responses to user queries, audio instructions or help utterances.
-    Assistant = 16,
+    Assistant,
 
     /// Use this in case of playing sounds in an emergency.
     /// Privileged MODIFY_AUDIO_ROUTING permission required.
-    Emergency = 1000,
+    Emergency,
 
     /// Use this for safety sounds and alerts, for example backup camera obstacle detection.
     /// Privileged MODIFY_AUDIO_ROUTING permission required.
-    Safety = 1001,
+    Safety,
 
     /// Use this for vehicle status alerts and information, for example the check engine light.
     /// Privileged MODIFY_AUDIO_ROUTING permission required.
-    VehicleStatus = 1002,
+    VehicleStatus,
 
     /// Use this for traffic announcements, etc.
     /// Privileged MODIFY_AUDIO_ROUTING permission required.
-    Announcement = 1003,
+    Announcement,
+
+    /// A value that is not known to this version of the crate.
+    Unknown(i32),
 }
 
+enum_values!(Usage {
+    Media = 1,
+    VoiceCommunication = 2,
+    VoiceCommunicationSignalling = 3,
+    Alarm = 4,
+    Notification = 5,
+    NotificationRingtone = 6,
+    NotificationEvent = 10,
+    AssistanceAccessibility = 11,
+    AssistanceNavigationGuidance = 12,
+    AssistanceSonification = 13,
+    Game = 14,
+    Assistant = 16,
+    Emergency = 1000,
+    Safety = 1001,
+    VehicleStatus = 1002,
+    Announcement = 1003,
+});
+
 /// Defines the audio source.
 /// An audio source defines both a default physical source of audio signal, and a recording
 /// configuration.
@@ -267,31 +381,44 @@ pub enum Usage {
 ///
 /// Added in API level 28.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum InputPreset {
     /// Use this preset when other presets do not apply.
-    Generic = 1,
+    Generic,
 
     /// Use this preset when recording video.
-    Camcorder = 5,
+    Camcorder,
 
     /// Use this preset when doing speech recognition.
-    VoiceRecognition = 6,
+    VoiceRecognition,
 
     /// Use this preset when doing telephony or voice messaging.
-    VoiceCommunication = 7,
+    VoiceCommunication,
 
     /// Use this preset to obtain an input with no effects.
     /// Note that this input will not have automatic gain control
     /// so the recorded volume may be very low.
-    Unprocessed = 9,
+    Unprocessed,
 
     /// Use this preset for capturing audio meant to be processed in real time
     /// and played back for live performance (e.g karaoke).
     /// The capture path will minimize latency and coupling with playback path.
     /// Available since API level 29.
-    VoicePerformance = 10,
+    VoicePerformance,
+
+    /// A value that is not known to this version of the crate.
+    Unknown(i32),
 }
 
+enum_values!(InputPreset {
+    Generic = 1,
+    Camcorder = 5,
+    VoiceRecognition = 6,
+    VoiceCommunication = 7,
+    Unprocessed = 9,
+    VoicePerformance = 10,
+});
+
 /// The ContentType attribute describes "what" you are playing.
 /// It expresses the general category of the content. This information is optional.
 /// But in case it is known (for instance `Movie` for a
@@ -304,21 +431,32 @@ pub enum InputPreset {
 ///
 /// Added in API level 28.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum ContentType {
     /// Use this for spoken voice, audio books, etcetera.
-    Speech = 1,
+    Speech,
 
     /// Use this for pre-recorded or live music.
-    Music = 2,
+    Music,
 
     /// Use this for a movie or video soundtrack.
-    Movie = 3,
+    Movie,
 
     /// Use this for sound is designed to accompany a user action,
     /// such as a click or beep sound made when the user presses a button.
-    Sonification = 4,
+    Sonification,
+
+    /// A value that is not known to this version of the crate.
+    Unknown(i32),
 }
 
+enum_values!(ContentType {
+    Speech = 1,
+    Music = 2,
+    Movie = 3,
+    Sonification = 4,
+});
+
 /// Specifying if audio may or may not be captured by other apps or the system.
 ///
 /// Note that these match the equivalent values in android.media.AudioAttributes
@@ -326,6 +464,7 @@ pub enum ContentType {
 ///
 /// Added in API level 29.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum AllowedCapturePolicy {
     /// Indicates that the audio may be captured by any app.
     ///
@@ -335,7 +474,7 @@ pub enum AllowedCapturePolicy {
     /// On Android Q, this means only `Media` and `Game` may be captured.
     ///
     /// See android.media.AudioAttributes#ALLOW_CAPTURE_BY_ALL.
-    AllowCaptureByAll = 1,
+    AllowCaptureByAll,
 
     /// Indicates that the audio may only be captured by system apps.
     ///
@@ -343,43 +482,85 @@ pub enum AllowedCapturePolicy {
     /// but have strong restriction. See
     /// android.media.AudioAttributes#ALLOW_CAPTURE_BY_SYSTEM for what the system apps
     /// can do with the capture audio.
-    AllowCaptureBySystem = 2,
+    AllowCaptureBySystem,
 
     /// Indicates that the audio may not be recorded by any app, even if it is a system app.
     ///
     /// It is encouraged to use `AllowCaptureBySystem` instead of this value as system apps
     /// provide significant and useful features for the user (eg. accessibility).
     /// See android.media.AudioAttributes#ALLOW_CAPTURE_BY_NONE.
+    AllowCaptureByNone,
+
+    /// A value that is not known to this version of the crate.
+    Unknown(i32),
+}
+
+enum_values!(AllowedCapturePolicy {
+    AllowCaptureByAll = 1,
+    AllowCaptureBySystem = 2,
     AllowCaptureByNone = 3,
+});
+
+/// Specifies whether audio may or may not be spatialized.
+///
+/// Added in API level 32.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SpatializationBehavior {
+    /// Indicates that the audio content associated with the stream will follow the default
+    /// platform behavior with regards to which content will be spatialized or not.
+    Auto,
+
+    /// Indicates that the audio content associated with the stream should never be spatialized.
+    Never,
+
+    /// A value that is not known to this version of the crate.
+    Unknown(i32),
 }
 
+enum_values!(SpatializationBehavior {
+    Auto = 1,
+    Never = 2,
+});
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum PerformanceMode {
     /// No particular performance needs. Default.
-    None = 10,
+    None,
 
     /// Extending battery life is more important than low latency.
     ///
     /// This mode is not supported in input streams.
     /// For input, mode NONE will be used if this is requested.
-    PowerSaving = 11,
+    PowerSaving,
 
     /// Reducing latency is more important than battery life.
-    LowLatency = 12,
-}
+    LowLatency,
 
-impl PerformanceMode {
-    fn from_i32(val: i32) -> Self {
-        match val {
-            11 => Self::PowerSaving,
-            12 => Self::LowLatency,
-            _ => Self::None,
-        }
-    }
+    /// Extending battery life is more important than low latency, and the data is
+    /// offloaded to the audio hardware, which may decode compressed formats.
+    ///
+    /// This mode is not supported in input streams.
+    /// For input, mode NONE will be used if this is requested.
+    ///
+    /// Available since API level 36.
+    PowerSavingOffloaded,
+
+    /// A value that is not known to this version of the crate.
+    Unknown(i32),
 }
 
+enum_values!(PerformanceMode {
+    None = 10,
+    PowerSaving = 11,
+    LowLatency = 12,
+    PowerSavingOffloaded = 13,
+});
+
 /// Value returned the data callback function.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum CallbackResult {
     /// Continue calling the callback.
     Continue,
@@ -391,21 +572,100 @@ pub enum CallbackResult {
     Stop,
 }
 
-fn wrap_result(result: i32) -> Result<(), Error> {
+impl From<CallbackResult> for i32 {
+    fn from(val: CallbackResult) -> i32 {
+        match val {
+            CallbackResult::Continue => 0,
+            CallbackResult::Stop => 1,
+        }
+    }
+}
+
+fn wrap_result(result: i32, operation: &'static str) -> Result<(), Error> {
     if result < 0 {
-        Err(Error::from_code(result))
+        Err(Error::from_code(result, operation))
     } else {
         Ok(())
     }
 }
 
+/// Like `wrap_result`, but also records the state and the configuration of the stream.
+fn wrap_stream_result(
+    raw: *mut AAudioStreamRaw,
+    result: i32,
+    operation: &'static str,
+) -> Result<(), Error> {
+    wrap_result(result, operation).map_err(|error| error.with_stream(raw))
+}
+
+/// Fails with `ErrorKind::FunctionUnavailable` if the AAudio function is missing on this device,
+/// which is only detected with the `dynamic` feature.
+fn require(function: &'static str, operation: &'static str) -> Result<(), Error> {
+    if ffi::is_available(function) {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::FunctionUnavailable(function),
+            operation,
+        ))
+    }
+}
+
+/// Fails with `ResultCode::IllegalArgument` if `value` contains a nul byte.
+fn to_c_string(value: &str, operation: &'static str) -> Result<CString, Error> {
+    CString::new(value)
+        .map_err(|_| Error::new(ErrorKind::AAudio(ResultCode::IllegalArgument), operation))
+}
+
+type DataCallback = dyn FnMut(&StreamView<'_>, &mut [u8], i32) -> CallbackResult + Send + 'static;
+type PartialDataCallback = dyn FnMut(&StreamView<'_>, &mut [u8], i32) -> i32 + Send + 'static;
+type ErrorCallback = dyn FnMut(&StreamView<'_>, &StreamControl, Error) + Send + 'static;
+type PresentationEndCallback = dyn FnMut(&StreamView<'_>) + Send + 'static;
+type RouteChangeCallback = dyn FnMut(&StreamView<'_>) + Send + 'static;
+
+#[derive(Default)]
 struct StreamCallbacks {
-    _data_callback:
-        Box<Box<dyn FnMut(&AAudioStreamInfo, &mut [u8], i32) -> CallbackResult + Send + 'static>>,
-    _error_callback: Box<Box<dyn FnMut(&AAudioStreamInfo, Error) + Send + 'static>>,
+    _data_callback: Option<Box<Handler<DataCallback>>>,
+    _partial_data_callback: Option<Box<Handler<PartialDataCallback>>>,
+    _error_callback: Option<Box<ErrorHandler>>,
+    _presentation_end_callback: Option<Box<Handler<PresentationEndCallback>>>,
+    _callback: Option<Box<CallbackObject>>,
+    /// Shared by the callbacks above, which point to it.
+    panic: Box<PanicHandler>,
+}
+
+/// A callback together with the panic handler of its stream.
+struct Handler<C: ?Sized> {
+    callback: Box<C>,
+    panic: *const PanicHandler,
+}
+
+/// An error callback together with the stream it is registered for.
+struct ErrorHandler {
+    /// Locked by AAudio calling the callback and by panics reported to the callback.
+    callback: Mutex<Box<ErrorCallback>>,
+    panic: *const PanicHandler,
+    /// Set once the stream is opened.
+    stream: OnceLock<Weak<StreamInner>>,
+}
+
+impl ErrorHandler {
+    fn attach(&self, stream: Weak<StreamInner>) {
+        let _ = self.stream.set(stream);
+    }
+
+    /// Calls the callback with `error` of the `raw` stream, which must stay open
+    /// during the call.
+    unsafe fn call(&self, raw: *mut AAudioStreamRaw, error: Error) {
+        let stream = StreamView::new(raw);
+        let control = StreamControl::new(self.stream.get().cloned().unwrap_or_default());
+        let mut callback = self.callback.lock().unwrap_or_else(PoisonError::into_inner);
+        (*self.panic).catch_other("error_callback", || callback(&stream, &control, error));
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum StreamState {
     Uninitialized,
     Unknown,
@@ -421,255 +681,136 @@ pub enum StreamState {
     Closing,
     Closed,
     Disconnected,
-}
 
-impl StreamState {
-    fn from_i32(val: i32) -> Self {
-        match val {
-            0 => Self::Uninitialized,
-            1 => Self::Unknown,
-            2 => Self::Open,
-            3 => Self::Starting,
-            4 => Self::Started,
-            5 => Self::Pausing,
-            6 => Self::Paused,
-            7 => Self::Flushing,
-            8 => Self::Flushed,
-            9 => Self::Stopping,
-            10 => Self::Stopped,
-            11 => Self::Closing,
-            12 => Self::Closed,
-            13 => Self::Disconnected,
-            state => panic!("Unexpected stream state: {}", state),
-        }
-    }
+    /// A state that is not known to this version of the crate.
+    /// Not to be confused with `Unknown`, which is reported by AAudio itself.
+    Other(i32),
 }
 
-pub struct Timestamp {
-    pub frame_position: i64,
-    pub time_nanos: i64,
+enum_values!(StreamState {
+    Uninitialized = 0,
+    Unknown = 1,
+    Open = 2,
+    Starting = 3,
+    Started = 4,
+    Pausing = 5,
+    Paused = 6,
+    Flushing = 7,
+    Flushed = 8,
+    Stopping = 9,
+    Stopped = 10,
+    Closing = 11,
+    Closed = 12,
+    Disconnected = 13,
+} else Other);
+
+/// The configuration of an open stream, as returned from `StreamProperties::get_config()`.
+///
+/// The values added after API level 26 are `None` if their getter is not available on this
+/// device. They are also `None` in the configuration recorded by an `Error`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct StreamConfig {
+    pub device_id: i32,
+    pub direction: Direction,
+    pub sharing_mode: SharingMode,
+    pub performance_mode: PerformanceMode,
+    pub format: Format,
+    pub sample_rate: i32,
+    pub channel_count: i32,
+    pub frames_per_burst: i32,
+    pub buffer_capacity_in_frames: i32,
+    /// Available since API level 28.
+    pub usage: Option<Usage>,
+    /// Available since API level 28.
+    pub content_type: Option<ContentType>,
+    /// Available since API level 28.
+    pub input_preset: Option<InputPreset>,
+    /// Available since API level 29.
+    pub allowed_capture_policy: Option<AllowedCapturePolicy>,
+    /// Available since API level 30.
+    pub privacy_sensitive: Option<bool>,
+    /// Available since API level 32.
+    pub spatialization_behavior: Option<SpatializationBehavior>,
+    /// Available since API level 32.
+    pub content_spatialized: Option<bool>,
 }
 
-pub struct AAudioStream {
-    raw: *mut AAudioStreamRaw,
-    _callbacks: Option<StreamCallbacks>,
+/// The format of the audio hardware, as returned from `StreamProperties::get_hardware_info()`.
+///
+/// If it differs from the format of the stream, the framework converts the data between them.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct HardwareInfo {
+    pub channel_count: i32,
+    pub sample_rate: i32,
+    pub format: Format,
 }
 
-unsafe impl Send for AAudioStream {}
+/// The clock used for the time of a `Timestamp`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Clock {
+    /// `CLOCK_MONOTONIC`, which does not advance while the device is suspended.
+    Monotonic,
 
-fn get_timestamp_monotonic(raw: *mut AAudioStreamRaw) -> Result<Timestamp, Error> {
-    let mut frame_position = MaybeUninit::uninit();
-    let mut time_nanos = MaybeUninit::uninit();
-    let result = unsafe {
-        ffi::AAudioStream_getTimestamp(
-            raw,
-            libc::CLOCK_MONOTONIC,
-            frame_position.as_mut_ptr(),
-            time_nanos.as_mut_ptr(),
-        )
-    };
-    wrap_result(result)?;
-    Ok(unsafe {
-        Timestamp {
-            frame_position: frame_position.assume_init(),
-            time_nanos: time_nanos.assume_init(),
-        }
-    })
+    /// `CLOCK_BOOTTIME`, which includes the time the device was suspended.
+    /// This is the clock used for sensor and camera timestamps.
+    Boottime,
 }
 
-impl AAudioStream {
-    /// Returns the actual sample rate.
-    ///
-    /// Available since API level 26.
-    pub fn get_sample_rate(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getSampleRate(self.raw) }
-    }
-
-    /// A stream has one or more channels of data.
-    /// A frame will contain one sample for each channel.
-    ///
-    /// Available since API level 26.
-    pub fn get_channel_count(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getChannelCount(self.raw) }
-    }
-
-    /// Query the maximum number of frames that can be filled without blocking.
-    ///
-    /// Available since API level 26.
-    pub fn get_buffer_size_in_frames(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getBufferSizeInFrames(self.raw) }
-    }
-
-    /// Query the number of frames that the application should read or write at
-    /// one time for optimal performance. It is OK if an application writes
-    /// a different number of frames. But the buffer size may need to be larger
-    /// in order to avoid underruns or overruns.
-    ///
-    /// Note that this may or may not match the actual device burst size.
-    /// For some endpoints, the burst size can vary dynamically.
-    /// But these tend to be devices with high latency.
-    ///
-    /// Available since API level 26.
-    pub fn get_frames_per_burst(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getFramesPerBurst(self.raw) }
-    }
-
-    /// Query maximum buffer capacity in frames.
-    ///
-    /// Available since API level 26.
-    pub fn get_buffer_capacity_in_frames(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getBufferCapacityInFrames(self.raw) }
-    }
-
-    /// Query the size of the buffer that will be passed to the dataProc callback
-    /// in the numFrames parameter.
-    ///
-    /// This call can be used if the application needs to know the value of numFrames before
-    /// the stream is started. This is not normally necessary.
-    ///
-    /// If a specific size was requested by calling
-    /// `AAudioStreamBuilder::set_frames_per_data_callback()` then this will be the same size.
-    ///
-    /// If `AAudioStreamBuilder_set_frames_per_data_callback()` was not called then this will
-    /// return the size chosen by AAudio, or 0.
-    ///
-    /// 0 indicates that the callback buffer size for this stream
-    /// may vary from one dataProc callback to the next.
-    ///
-    /// Available since API level 26.
-    pub fn get_frames_per_data_callback(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getFramesPerDataCallback(self.raw) }
-    }
-
-    /// An XRun is an Underrun or an Overrun.
-    /// During playing, an underrun will occur if the stream is not written in time
-    /// and the system runs out of valid data.
-    /// During recording, an overrun will occur if the stream is not read in time
-    /// and there is no place to put the incoming data so it is discarded.
-    ///
-    /// An underrun or overrun can cause an audible "pop" or "glitch".
-    ///
-    /// Note that some INPUT devices may not support this function.
-    /// In that case a 0 will always be returned.
-    ///
-    /// Available since API level 26.
-    pub fn get_x_run_count(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getXRunCount(self.raw) }
-    }
-
-    /// Returns the actual device ID.
-    ///
-    /// Available since API level 26.
-    pub fn get_device_id(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getDeviceId(self.raw) }
-    }
-
-    /// Returns the actual data format.
-    ///
-    /// Available since API level 26.
-    pub fn get_format(&self) -> Format {
-        let val = unsafe { ffi::AAudioStream_getFormat(self.raw) };
-        Format::from_i32(val)
-    }
-
-    /// Provide actual sharing mode.
-    ///
-    /// Available since API level 26.
-    pub fn get_sharing_mode(&self) -> SharingMode {
-        let val = unsafe { ffi::AAudioStream_getSharingMode(self.raw) };
-        SharingMode::from_i32(val)
+impl Clock {
+    fn clock_id(self) -> libc::clockid_t {
+        match self {
+            Self::Monotonic => libc::CLOCK_MONOTONIC,
+            Self::Boottime => libc::CLOCK_BOOTTIME,
+        }
     }
+}
 
-    /// Get the performance mode used by the stream.
-    ///
-    /// Available since API level 26.
-    pub fn get_performance_mode(&self) -> PerformanceMode {
-        let val = unsafe { ffi::AAudioStream_getPerformanceMode(self.raw) };
-        PerformanceMode::from_i32(val)
-    }
+/// The time at which a particular frame was presented, as returned from
+/// `StreamProperties::get_timestamp()`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Timestamp {
+    pub frame_position: i64,
+    /// The time in nanoseconds on the clock the timestamp was requested with.
+    pub time_nanos: i64,
+}
 
-    /// Available since API level 26.
-    pub fn get_direction(&self) -> Direction {
-        let val = unsafe { ffi::AAudioStream_getDirection(self.raw) };
-        Direction::from_i32(val)
+impl Timestamp {
+    /// Returns the time at which the frame was presented, since the start of the clock.
+    pub fn time(&self) -> Duration {
+        Duration::from_nanos(self.time_nanos.max(0) as u64)
     }
+}
 
-    /// Returns the number of frames that have been written since the stream was created.
-    /// For an output stream, this will be advanced by the application calling write()
-    /// or by a data callback.
-    /// For an input stream, this will be advanced by the endpoint.
-    ///
-    /// The frame position is monotonically increasing.
-    ///
-    /// Available since API level 26.
-    pub fn get_frames_written(&self) -> i64 {
-        unsafe { ffi::AAudioStream_getFramesWritten(self.raw) }
+impl From<Timestamp> for Duration {
+    fn from(timestamp: Timestamp) -> Duration {
+        timestamp.time()
     }
+}
 
-    /// Returns the number of frames that have been read since the stream was created.
-    /// For an output stream, this will be advanced by the endpoint.
-    /// For an input stream, this will be advanced by the application calling read()
-    /// or by a data callback.
-    ///
-    /// The frame position is monotonically increasing.
-    ///
-    /// Available since API level 26.
-    pub fn get_frames_read(&self) -> i64 {
-        unsafe { ffi::AAudioStream_getFramesRead(self.raw) }
-    }
+pub struct AAudioStream {
+    inner: Arc<StreamInner>,
+}
 
-    /// Passes back the session ID associated with this stream.
-    ///
-    /// The session ID can be used to associate a stream with effects processors.
-    /// The effects are controlled using the Android AudioEffect Java API.
-    ///
-    /// If `AAudioStreamBuilder::set_session_id()` was called with 0
-    /// then a new session ID should be allocated once when the stream is opened.
-    ///
-    /// If `AAudioStreamBuilder::set_session_id()` was called with a previously allocated
-    /// session ID then that value should be returned.
-    ///
-    /// If `AAudioStreamBuilder::set_session_id()` was not called then this function should
-    /// return -1.
-    ///
-    /// The sessionID for a stream should not change once the stream has been opened.
-    ///
-    /// Available since API level 28.
-    pub fn get_session_id(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getSessionId(self.raw) }
-    }
+unsafe impl Send for AAudioStream {}
 
-    /// Returns the time at which a particular frame was presented.
-    /// This can be used to synchronize audio with video or MIDI.
-    /// It can also be used to align a recorded stream with a playback stream.
-    ///
-    /// Timestamps are only valid when the stream is in `Started` state.
-    /// `InvalidState` will be returned if the stream is not started.
-    /// Note that because request_start() is asynchronous, timestamps will not be valid until
-    /// a short time after calling request_start().
-    /// So `InvalidState` should not be considered a fatal error.
-    /// Just try calling again later.
-    ///
-    /// If an error occurs, then the position and time will not be modified.
-    ///
-    /// The position and time passed back are monotonically increasing.
+impl AAudioStream {
+    /// Returns a handle to the stream that can be cloned and sent to other threads
+    /// to query and control the stream.
     ///
-    /// Available since API level 26.
-    pub fn get_timestamp_monotonic(&self) -> Result<Timestamp, Error> {
-        get_timestamp_monotonic(self.raw)
+    /// The stream is closed once the `AAudioStream` and all of its handles are dropped.
+    pub fn handle(&self) -> StreamHandle {
+        StreamHandle::new(self.inner.clone())
     }
 
-    /// Query the current state of the client, eg. `Pausing`.
-    ///
-    /// This function will immediately return the state without updating the state.
-    /// If you want to update the client state based on the server state then
-    /// call `AAudioStream::wait_for_state_change()` with currentState
-    /// set to `Unknown` and a zero timeout.
+    /// Takes the payload of the oldest panic of the callbacks that could not be reported,
+    /// because neither a panic hook nor an error callback was set to receive it.
     ///
-    /// Available since API level 26.
-    pub fn get_state(&self) -> StreamState {
-        let val = unsafe { ffi::AAudioStream_getState(self.raw) };
-        StreamState::from_i32(val)
+    /// The errors that the crate detects while running the callbacks are kept the same way,
+    /// with an `Error` as payload. See `AAudioStreamBuilder::set_panic_hook()`.
+    pub fn take_panic(&self) -> Option<Box<dyn Any + Send>> {
+        self.inner.panic_handler().take_unreported()
     }
 
     /// Free the audio resources associated with the stream.
@@ -681,9 +822,9 @@ impl AAudioStream {
     /// happens if you are monitoring stream progress from a UI thread.
     ///
     /// Available since API level 30.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
     pub fn release(&mut self) -> Result<(), Error> {
-        let val = unsafe { ffi::AAudioStream_release(self.raw) };
-        wrap_result(val)
+        self.inner.release()
     }
 
     /// Asynchronously request to start playing the stream. For output streams, one should
@@ -695,8 +836,7 @@ impl AAudioStream {
     ///
     /// Available since API level 26.
     pub fn request_start(&mut self) -> Result<(), Error> {
-        let val = unsafe { ffi::AAudioStream_requestStart(self.raw) };
-        wrap_result(val)
+        self.inner.request_start()
     }
 
     /// Asynchronous request for the stream to pause.
@@ -710,8 +850,7 @@ impl AAudioStream {
     ///
     /// Available since API level 26.
     pub fn request_pause(&mut self) -> Result<(), Error> {
-        let val = unsafe { ffi::AAudioStream_requestPause(self.raw) };
-        wrap_result(val)
+        self.inner.request_pause()
     }
 
     /// Asynchronous request for the stream to flush.
@@ -724,8 +863,7 @@ impl AAudioStream {
     ///
     /// Available since API level 26.
     pub fn request_flush(&mut self) -> Result<(), Error> {
-        let val = unsafe { ffi::AAudioStream_requestFlush(self.raw) };
-        wrap_result(val)
+        self.inner.request_flush()
     }
 
     /// Asynchronous request for the stream to stop.
@@ -734,8 +872,7 @@ impl AAudioStream {
     ///
     /// Available since API level 26.
     pub fn request_stop(&mut self) -> Result<(), Error> {
-        let val = unsafe { ffi::AAudioStream_requestStop(self.raw) };
-        wrap_result(val)
+        self.inner.request_stop()
     }
 
     /// Wait until the current state no longer matches the input state.
@@ -750,17 +887,7 @@ impl AAudioStream {
         input_state: StreamState,
         timeout_nanos: i64,
     ) -> Result<StreamState, Error> {
-        let mut new_state = MaybeUninit::uninit();
-        let result = unsafe {
-            ffi::AAudioStream_waitForStateChange(
-                self.raw,
-                input_state as i32,
-                new_state.as_mut_ptr(),
-                timeout_nanos,
-            )
-        };
-        wrap_result(result)?;
-        Ok(unsafe { StreamState::from_i32(new_state.assume_init()) })
+        self.inner.wait_for_state_change(input_state, timeout_nanos)
     }
 
     /// Read data from the stream.
@@ -783,6 +910,7 @@ impl AAudioStream {
     ///
     /// * `buffer` - The slice with the samples.
     /// * `num_frames` - Number of frames to read. Only complete frames will be written.
+    ///   Fails with `ResultCode::IllegalArgument` if `buffer` is too small for `num_frames` frames.
     /// * `timeout_nanoseconds` - Maximum number of nanoseconds to wait for completion.
     pub fn read(
         &mut self,
@@ -790,15 +918,18 @@ impl AAudioStream {
         num_frames: i32,
         timeout_nanoseconds: i64,
     ) -> Result<u32, Error> {
+        self.check_byte_buffer(buffer.len(), num_frames, "read")?;
+        let _io = self.inner.lock_io();
+        let raw = self.raw();
         let result = unsafe {
             ffi::AAudioStream_read(
-                self.raw,
+                raw,
                 buffer.as_mut_ptr() as *mut c_void,
                 num_frames,
                 timeout_nanoseconds,
             )
         };
-        wrap_result(result)?;
+        wrap_stream_result(raw, result, "read")?;
         Ok(result as u32)
     }
 
@@ -822,6 +953,7 @@ impl AAudioStream {
     ///
     /// * `buffer` - The address of the first sample.
     /// * `num_frames` - Number of frames to write. Only complete frames will be written.
+    ///   Fails with `ResultCode::IllegalArgument` if `buffer` is too small for `num_frames` frames.
     /// * `timeout_nanoseconds` - Maximum number of nanoseconds to wait for completion.
     pub fn write(
         &mut self,
@@ -829,256 +961,305 @@ impl AAudioStream {
         num_frames: i32,
         timeout_nanoseconds: i64,
     ) -> Result<u32, Error> {
+        self.check_byte_buffer(buffer.len(), num_frames, "write")?;
+        let _io = self.inner.lock_io();
+        let raw = self.raw();
         let result = unsafe {
             ffi::AAudioStream_write(
-                self.raw,
+                raw,
                 buffer.as_ptr() as *const c_void,
                 num_frames,
                 timeout_nanoseconds,
             )
         };
-        wrap_result(result)?;
+        wrap_stream_result(raw, result, "write")?;
         Ok(result as u32)
     }
 
-    /// This can be used to adjust the latency of the buffer by changing
-    /// the threshold where blocking will occur.
-    /// By combining this with `AAudioStream::get_x_run_count()`, the latency can be tuned
-    /// at run-time for each device.
-    /// Returns actual buffer size in frames or a negative error.
+    /// Read frames from the stream into `buffer`.
+    /// Returns the number of frames actually read.
     ///
-    /// This cannot be set higher than `AAudioStream::get_buffer_capacity_in_frames()`.
+    /// The number of frames to read is the number of frames in `buffer`.
+    /// `F` must match the format of the stream, or this fails with `ErrorKind::FormatMismatch`.
+    /// If `F` is a frame type, it must match the channel count of the stream, or this fails
+    /// with `ErrorKind::ChannelCountMismatch`. If `F` is a `Sample` type, the length of `buffer`
+    /// must be a multiple of the channel count, or this fails with `ErrorKind::BufferLength`.
     ///
-    /// Note that you will probably not get the exact size you request.
-    /// You can check the return value or call `AAudioStream::get_buffer_size_in_frames()`
-    /// to see what the actual final size is.
+    /// See `AAudioStream::read()` for the blocking behaviour.
     ///
     /// Available since API level 26.
     ///
     /// # Arguments
     ///
-    /// * `num_frames` - requested number of frames that can be filled without blocking
-    pub fn set_buffer_size_in_frames(&mut self, num_frames: i32) -> Result<(), Error> {
-        let result = unsafe { ffi::AAudioStream_setBufferSizeInFrames(self.raw, num_frames) };
-        wrap_result(result)
-    }
-}
-
-impl Drop for AAudioStream {
-    fn drop(&mut self) {
-        unsafe {
-            ffi::AAudioStream_close(self.raw);
-        }
-    }
-}
-
-/// Passed as a callback parameter, providing operations that are safe
-/// to perform from callback invocation.
-pub struct AAudioStreamInfo {
-    raw: *mut AAudioStreamRaw,
-}
-
-unsafe impl Send for AAudioStreamInfo {}
-
-impl AAudioStreamInfo {
-    /// Returns the actual sample rate.
-    ///
-    /// Available since API level 26.
-    pub fn get_sample_rate(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getSampleRate(self.raw) }
-    }
-
-    /// A stream has one or more channels of data.
-    /// A frame will contain one sample for each channel.
-    ///
-    /// Available since API level 26.
-    pub fn get_channel_count(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getChannelCount(self.raw) }
-    }
-
-    /// Query the maximum number of frames that can be filled without blocking.
-    ///
-    /// Available since API level 26.
-    pub fn get_buffer_size_in_frames(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getBufferSizeInFrames(self.raw) }
-    }
-
-    /// Query the number of frames that the application should read or write at
-    /// one time for optimal performance. It is OK if an application writes
-    /// a different number of frames. But the buffer size may need to be larger
-    /// in order to avoid underruns or overruns.
-    ///
-    /// Note that this may or may not match the actual device burst size.
-    /// For some endpoints, the burst size can vary dynamically.
-    /// But these tend to be devices with high latency.
-    ///
-    /// Available since API level 26.
-    pub fn get_frames_per_burst(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getFramesPerBurst(self.raw) }
-    }
-
-    /// Query maximum buffer capacity in frames.
-    ///
-    /// Available since API level 26.
-    pub fn get_buffer_capacity_in_frames(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getBufferCapacityInFrames(self.raw) }
+    /// * `buffer` - The slice to read the frames into.
+    /// * `timeout_nanoseconds` - Maximum number of nanoseconds to wait for completion.
+    pub fn read_frames<F: AudioFrame>(
+        &mut self,
+        buffer: &mut [F],
+        timeout_nanoseconds: i64,
+    ) -> Result<u32, Error> {
+        let num_frames = self.frame_count::<F>(buffer.len(), "read_frames")?;
+        let _io = self.inner.lock_io();
+        let raw = self.raw();
+        let result = unsafe {
+            ffi::AAudioStream_read(
+                raw,
+                buffer.as_mut_ptr() as *mut c_void,
+                num_frames,
+                timeout_nanoseconds,
+            )
+        };
+        wrap_stream_result(raw, result, "read_frames")?;
+        Ok(result as u32)
     }
 
-    /// Query the size of the buffer that will be passed to the dataProc callback
-    /// in the numFrames parameter.
-    ///
-    /// This call can be used if the application needs to know the value of numFrames before
-    /// the stream is started. This is not normally necessary.
+    /// Write the frames of `buffer` to the stream.
+    /// Returns the number of frames actually written.
     ///
-    /// If a specific size was requested by calling
-    /// `AAudioStreamBuilder::set_frames_per_data_callback()` then this will be the same size.
+    /// The number of frames to write is the number of frames in `buffer`.
+    /// `F` must match the format of the stream, or this fails with `ErrorKind::FormatMismatch`.
+    /// If `F` is a frame type, it must match the channel count of the stream, or this fails
+    /// with `ErrorKind::ChannelCountMismatch`. If `F` is a `Sample` type, the length of `buffer`
+    /// must be a multiple of the channel count, or this fails with `ErrorKind::BufferLength`.
     ///
-    /// If `AAudioStreamBuilder::set_frames_per_data_callback()` was not called then this will
-    /// return the size chosen by AAudio, or 0.
-    ///
-    /// 0 indicates that the callback buffer size for this stream
-    /// may vary from one dataProc callback to the next.
+    /// See `AAudioStream::write()` for the blocking behaviour.
     ///
     /// Available since API level 26.
-    pub fn get_frames_per_data_callback(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getFramesPerDataCallback(self.raw) }
-    }
-
-    /// An XRun is an Underrun or an Overrun.
-    /// During playing, an underrun will occur if the stream is not written in time
-    /// and the system runs out of valid data.
-    /// During recording, an overrun will occur if the stream is not read in time
-    /// and there is no place to put the incoming data so it is discarded.
     ///
-    /// An underrun or overrun can cause an audible "pop" or "glitch".
-    ///
-    /// Note that some INPUT devices may not support this function.
-    /// In that case a 0 will always be returned.
-    ///
-    /// Available since API level 26.
-    pub fn get_x_run_count(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getXRunCount(self.raw) }
-    }
-
-    /// Returns the actual device ID.
+    /// # Arguments
     ///
-    /// Available since API level 26.
-    pub fn get_device_id(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getDeviceId(self.raw) }
+    /// * `buffer` - The frames to write.
+    /// * `timeout_nanoseconds` - Maximum number of nanoseconds to wait for completion.
+    pub fn write_frames<F: AudioFrame>(
+        &mut self,
+        buffer: &[F],
+        timeout_nanoseconds: i64,
+    ) -> Result<u32, Error> {
+        let num_frames = self.frame_count::<F>(buffer.len(), "write_frames")?;
+        let _io = self.inner.lock_io();
+        let raw = self.raw();
+        let result = unsafe {
+            ffi::AAudioStream_write(
+                raw,
+                buffer.as_ptr() as *const c_void,
+                num_frames,
+                timeout_nanoseconds,
+            )
+        };
+        wrap_stream_result(raw, result, "write_frames")?;
+        Ok(result as u32)
     }
 
-    /// Returns the actual data format.
-    ///
-    /// Available since API level 26.
-    pub fn get_format(&self) -> Format {
-        let val = unsafe { ffi::AAudioStream_getFormat(self.raw) };
-        Format::from_i32(val)
+    /// Returns an error of the given kind that records the state and the configuration
+    /// of the stream.
+    fn error(&self, kind: ErrorKind, operation: &'static str) -> Error {
+        Error::new(kind, operation).with_stream(self.raw())
     }
 
-    /// Provide actual sharing mode.
-    ///
-    /// Available since API level 26.
-    pub fn get_sharing_mode(&self) -> SharingMode {
-        let val = unsafe { ffi::AAudioStream_getSharingMode(self.raw) };
-        SharingMode::from_i32(val)
+    /// Fails if a byte buffer of `length` bytes cannot hold `num_frames` frames of the stream.
+    fn check_byte_buffer(
+        &self,
+        length: usize,
+        num_frames: i32,
+        operation: &'static str,
+    ) -> Result<(), Error> {
+        let frame_size = self.get_format().frame_size(self.get_channel_count());
+        if frame_size <= 0 {
+            return Err(self.error(ErrorKind::AAudio(ResultCode::InvalidFormat), operation));
+        }
+        match byte_length(num_frames, frame_size) {
+            Some(required) if required <= length => Ok(()),
+            _ => Err(self.error(ErrorKind::AAudio(ResultCode::IllegalArgument), operation)),
+        }
     }
 
-    /// Get the performance mode used by the stream.
-    ///
-    /// Available since API level 26.
-    pub fn get_performance_mode(&self) -> PerformanceMode {
-        let val = unsafe { ffi::AAudioStream_getPerformanceMode(self.raw) };
-        PerformanceMode::from_i32(val)
+    /// Fails if `F` does not match the format or the channel count of the stream.
+    fn check_frame_type<F: AudioFrame>(&self, operation: &'static str) -> Result<(), Error> {
+        let requested = <F::Sample as Sample>::FORMAT;
+        let granted = self.get_format();
+        if granted != requested {
+            return Err(self.error(ErrorKind::FormatMismatch { requested, granted }, operation));
+        }
+        if let Some(requested) = F::CHANNEL_COUNT {
+            let granted = self.get_channel_count();
+            if granted != requested {
+                return Err(self.error(
+                    ErrorKind::ChannelCountMismatch { requested, granted },
+                    operation,
+                ));
+            }
+        }
+        Ok(())
     }
 
-    /// Available since API level 26.
-    pub fn get_direction(&self) -> Direction {
-        let val = unsafe { ffi::AAudioStream_getDirection(self.raw) };
-        Direction::from_i32(val)
+    /// Returns the number of frames of the stream in a buffer of `length` values of `F`,
+    /// checking that `F` matches the format and the channel count of the stream.
+    fn frame_count<F: AudioFrame>(
+        &self,
+        length: usize,
+        operation: &'static str,
+    ) -> Result<i32, Error> {
+        self.check_frame_type::<F>(operation)?;
+        let channel_count = self.get_channel_count();
+        let num_frames = match F::CHANNEL_COUNT {
+            Some(_) => length,
+            None if channel_count <= 0 || !length.is_multiple_of(channel_count as usize) => {
+                return Err(self.error(
+                    ErrorKind::BufferLength {
+                        length,
+                        channel_count,
+                    },
+                    operation,
+                ));
+            }
+            None => length / channel_count as usize,
+        };
+        // Larger buffers are transferred partially, as if the call timed out.
+        Ok(num_frames.min(i32::MAX as usize) as i32)
     }
 
-    /// Returns the number of frames that have been written since the stream was created.
-    /// For an output stream, this will be advanced by the application calling `write()`
-    /// or by a data callback.
-    /// For an input stream, this will be advanced by the endpoint.
+    /// This can be used to adjust the latency of the buffer by changing
+    /// the threshold where blocking will occur.
+    /// By combining this with `StreamProperties::get_x_run_count()`, the latency can be tuned
+    /// at run-time for each device.
+    /// Returns actual buffer size in frames or a negative error.
+    ///
+    /// This cannot be set higher than `StreamProperties::get_buffer_capacity_in_frames()`.
     ///
-    /// The frame position is monotonically increasing.
+    /// Note that you will probably not get the exact size you request.
+    /// You can check the return value or call `StreamProperties::get_buffer_size_in_frames()`
+    /// to see what the actual final size is.
     ///
     /// Available since API level 26.
-    pub fn get_frames_written(&self) -> i64 {
-        unsafe { ffi::AAudioStream_getFramesWritten(self.raw) }
-    }
-
-    /// Returns the number of frames that have been read since the stream was created.
-    /// For an output stream, this will be advanced by the endpoint.
-    /// For an input stream, this will be advanced by the application calling `read()`
-    /// or by a data callback.
     ///
-    /// The frame position is monotonically increasing.
+    /// # Arguments
     ///
-    /// Available since API level 26.
-    pub fn get_frames_read(&self) -> i64 {
-        unsafe { ffi::AAudioStream_getFramesRead(self.raw) }
+    /// * `num_frames` - requested number of frames that can be filled without blocking
+    pub fn set_buffer_size_in_frames(&mut self, num_frames: i32) -> Result<(), Error> {
+        self.inner.set_buffer_size_in_frames(num_frames)
     }
 
-    /// Passes back the session ID associated with this stream.
-    ///
-    /// The session ID can be used to associate a stream with effects processors.
-    /// The effects are controlled using the Android AudioEffect Java API.
-    ///
-    /// If `AAudioStreamBuilder::set_session_id()` was called with 0
-    /// then a new session ID should be allocated once when the stream is opened.
+    /// Declares the delay and the padding of the compressed data played by an offloaded stream,
+    /// so that the decoder can skip the frames added by the encoder for gapless playback.
     ///
-    /// If `AAudioStreamBuilder::set_session_id()` was called with a previously allocated
-    /// session ID then that value should be returned.
+    /// Fails with `ResultCode::Unimplemented` if the stream is not offloaded and
+    /// with `ResultCode::IllegalArgument` if either value is negative.
     ///
-    /// If `AAudioStreamBuilder::set_session_id()` was not called then this function should
-    /// return -1.
+    /// Available since API level 36.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
     ///
-    /// The sessionID for a stream should not change once the stream has been opened.
+    /// # Arguments
     ///
-    /// Available since API level 28.
-    pub fn get_session_id(&self) -> i32 {
-        unsafe { ffi::AAudioStream_getSessionId(self.raw) }
+    /// * `delay_in_frames` - number of frames to skip at the beginning of the track
+    /// * `padding_in_frames` - number of frames to skip at the end of the track
+    pub fn set_offload_delay_padding(
+        &mut self,
+        delay_in_frames: i32,
+        padding_in_frames: i32,
+    ) -> Result<(), Error> {
+        require(
+            "AAudioStream_setOffloadDelayPadding",
+            "set_offload_delay_padding",
+        )?;
+        let raw = self.raw();
+        let result = unsafe {
+            ffi::AAudioStream_setOffloadDelayPadding(raw, delay_in_frames, padding_in_frames)
+        };
+        wrap_stream_result(raw, result, "set_offload_delay_padding")
     }
 
-    /// Returns the time at which a particular frame was presented.
-    /// This can be used to synchronize audio with video or MIDI.
-    /// It can also be used to align a recorded stream with a playback stream.
-    ///
-    /// Timestamps are only valid when the stream is in `Started` state.
-    /// `InvalidState` will be returned if the stream is not started.
-    /// Note that because request_start() is asynchronous, timestamps will not be valid until
-    /// a short time after calling request_start().
-    /// So `InvalidState` should not be considered a fatal error.
-    /// Just try calling again later.
+    /// Indicates that all the data of the current track has been written to an offloaded stream.
     ///
-    /// If an error occurs, then the position and time will not be modified.
+    /// The presentation end callback is called once that data has been played, see
+    /// `AAudioStreamBuilder::set_presentation_end_callback()`.
+    /// Data written after this call belongs to the next track.
     ///
-    /// The position and time passed back are monotonically increasing.
+    /// Fails with `ResultCode::Unimplemented` if the stream is not offloaded.
     ///
-    /// Available since API level 26.
-    pub fn get_timestamp_monotonic(&self) -> Result<Timestamp, Error> {
-        get_timestamp_monotonic(self.raw)
+    /// Available since API level 36.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    pub fn set_offload_end_of_stream(&mut self) -> Result<(), Error> {
+        require(
+            "AAudioStream_setOffloadEndOfStream",
+            "set_offload_end_of_stream",
+        )?;
+        let raw = self.raw();
+        let result = unsafe { ffi::AAudioStream_setOffloadEndOfStream(raw) };
+        wrap_stream_result(raw, result, "set_offload_end_of_stream")
     }
+}
 
-    /// Query the current state of the client, eg. `Pausing`.
-    ///
-    /// This function will immediately return the state without updating the state.
-    /// If you want to update the client state based on the server state then
-    /// call AAudioStream::wait_for_state_change() with currentState
-    /// set to `Unknown` and a zero timeout.
-    ///
-    /// Available since API level 26.
-    pub fn get_state(&self) -> StreamState {
-        let val = unsafe { ffi::AAudioStream_getState(self.raw) };
-        StreamState::from_i32(val)
+/// Passed as a callback parameter, providing operations that are safe
+/// to perform from callback invocation.
+///
+/// The view is only valid during the callback invocation, so it can neither be stored
+/// nor sent to other threads. Use `AAudioStream::handle()` to query the stream
+/// from other threads.
+pub struct StreamView<'a> {
+    raw: *mut AAudioStreamRaw,
+    _stream: PhantomData<&'a AAudioStreamRaw>,
+}
+
+impl<'a> StreamView<'a> {
+    /// The stream must stay open for the lifetime of the view.
+    unsafe fn new(raw: *mut AAudioStreamRaw) -> Self {
+        Self {
+            raw,
+            _stream: PhantomData,
+        }
     }
 }
 
 pub struct AAudioStreamBuilder {
     raw: *mut AAudioStreamBuilderRaw,
-    callbacks: Option<StreamCallbacks>,
+    callbacks: StreamCallbacks,
+    /// The format required by the typed data callback.
+    callback_format: Option<Format>,
+    /// The channel count required by the frame type of the typed data callback.
+    callback_channel_count: Option<i32>,
+    /// The channel mask requested with `set_channel_mask`, unless the channel count
+    /// was set afterwards.
+    channel_mask: Option<ChannelMask>,
+    /// Strings passed to AAudio, owned by the stream once it is opened.
+    package_name: Option<CString>,
+    attribution_tag: Option<CString>,
+    /// The error callback registered with AAudio and its user data.
+    error_callback: (ffi::ErrorCallback, *mut c_void),
+    /// Called after the stream is restarted, if auto restart is enabled.
+    route_change_callback: Option<Box<RouteChangeCallback>>,
+}
+
+/// Returns the number of bytes of `num_frames` frames of `frame_size` bytes,
+/// or `None` if either is not positive or the length overflows.
+fn byte_length(num_frames: i32, frame_size: i32) -> Option<usize> {
+    if num_frames < 0 || frame_size <= 0 {
+        return None;
+    }
+    (num_frames as usize).checked_mul(frame_size as usize)
+}
+
+/// Returns the buffer of `num_frames` frames passed to a data callback.
+///
+/// Reports an error and returns `None` if the length of the buffer is unknown,
+/// eg. because the stream has an unknown format.
+unsafe fn callback_buffer<'a>(
+    stream: &StreamView<'_>,
+    audio_data: *mut c_void,
+    num_frames: i32,
+    panic: &PanicHandler,
+) -> Option<&'a mut [u8]> {
+    let frame_size = stream.get_format().frame_size(stream.get_channel_count());
+    match byte_length(num_frames, frame_size) {
+        Some(length) => Some(std::slice::from_raw_parts_mut(
+            audio_data as *mut u8,
+            length,
+        )),
+        None => {
+            let kind = ErrorKind::AAudio(ResultCode::InvalidFormat);
+            panic.report_error(Error::new(kind, "data_callback"));
+            None
+        }
+    }
 }
 
 unsafe extern "C" fn raw_data_callback(
@@ -1087,22 +1268,41 @@ unsafe extern "C" fn raw_data_callback(
     audio_data: *mut c_void,
     num_frames: i32,
 ) -> i32 {
-    match std::panic::catch_unwind(|| {
-        let stream = AAudioStreamInfo { raw: stream };
-        let data: &mut [u8] = std::slice::from_raw_parts_mut(
-            audio_data as *mut u8,
-            (num_frames * stream.get_channel_count() * stream.get_format().sample_size()) as usize,
-        );
-        let callback =
-            user_data as *mut Box<dyn FnMut(&AAudioStreamInfo, &mut [u8], i32) -> CallbackResult>;
-        let callback = &mut *callback;
-        callback(&stream, data, num_frames) as i32
-    }) {
-        Ok(r) => r,
-        Err(e) => {
-            eprintln!("{:?}", e);
-            std::process::abort();
-        }
+    let stream = StreamView::new(stream);
+    let handler = &mut *(user_data as *mut Handler<DataCallback>);
+    let panic = &*handler.panic;
+    let data = match callback_buffer(&stream, audio_data, num_frames, panic) {
+        Some(data) => data,
+        None => return CallbackResult::Stop.into(),
+    };
+    let callback = &mut handler.callback;
+    match panic.catch_data(|| callback(&stream, data, num_frames)) {
+        Some(result) => result.into(),
+        None => panic.silence(data).into(),
+    }
+}
+
+unsafe extern "C" fn raw_partial_data_callback(
+    stream: *mut AAudioStreamRaw,
+    user_data: *mut c_void,
+    audio_data: *mut c_void,
+    num_frames: i32,
+) -> i32 {
+    let stream = StreamView::new(stream);
+    let handler = &mut *(user_data as *mut Handler<PartialDataCallback>);
+    let panic = &*handler.panic;
+    // A negative result stops the stream.
+    let data = match callback_buffer(&stream, audio_data, num_frames, panic) {
+        Some(data) => data,
+        None => return -1,
+    };
+    let callback = &mut handler.callback;
+    match panic.catch_data(|| callback(&stream, data, num_frames)) {
+        Some(result) => result,
+        None => match panic.silence(data) {
+            CallbackResult::Continue => num_frames,
+            _ => -1,
+        },
     }
 }
 
@@ -1111,28 +1311,54 @@ unsafe extern "C" fn raw_error_callback(
     user_data: *mut c_void,
     error: i32,
 ) {
-    if let Err(e) = std::panic::catch_unwind(|| {
-        let stream = AAudioStreamInfo { raw: stream };
-        let callback = user_data as *mut Box<dyn FnMut(&AAudioStreamInfo, Error)>;
-        let callback = &mut *callback;
-        callback(&stream, Error::from_code(error));
-    }) {
-        eprintln!("{:?}", e);
-        std::process::abort();
-    }
+    let handler = &*(user_data as *const ErrorHandler);
+    handler.call(
+        stream,
+        Error::from_code(error, "error_callback").with_stream(stream),
+    );
+}
+
+unsafe extern "C" fn raw_presentation_end_callback(
+    stream: *mut AAudioStreamRaw,
+    user_data: *mut c_void,
+) {
+    let stream = StreamView::new(stream);
+    let handler = &mut *(user_data as *mut Handler<PresentationEndCallback>);
+    let callback = &mut handler.callback;
+    (*handler.panic).catch_other("presentation_end_callback", || callback(&stream));
 }
 
 impl AAudioStreamBuilder {
     pub fn new() -> Result<Self, Error> {
         let mut raw = MaybeUninit::<*mut AAudioStreamBuilderRaw>::uninit();
         let result = unsafe { ffi::AAudio_createStreamBuilder(raw.as_mut_ptr()) };
-        wrap_result(result)?;
+        wrap_result(result, "new")?;
         Ok(Self {
             raw: unsafe { raw.assume_init() },
-            callbacks: None,
+            callbacks: StreamCallbacks::default(),
+            callback_format: None,
+            callback_channel_count: None,
+            channel_mask: None,
+            package_name: None,
+            attribution_tag: None,
+            error_callback: (None, std::ptr::null_mut()),
+            route_change_callback: None,
         })
     }
 
+    /// Fails with `ErrorKind::FunctionUnavailable` if the AAudio function is missing on this
+    /// device, returning the builder in the error.
+    fn require(
+        self,
+        function: &'static str,
+        operation: &'static str,
+    ) -> Result<Self, BuilderError> {
+        match require(function, operation) {
+            Ok(()) => Ok(self),
+            Err(error) => Err(BuilderError::new(self, error)),
+        }
+    }
+
     /// Request that AAudio call the `data_callback` when the stream is running and the
     /// `error_callback` if any error occurs or the stream is disconnected.
     ///
@@ -1174,6 +1400,8 @@ impl AAudioStreamBuilder {
     /// In response, this function should signal or create another thread to stop
     /// and close this stream. The other thread could then reopen a stream on another device.
     /// Do not stop or close the stream, or reopen the new stream, directly from this callback.
+    /// Instead, the `StreamControl` passed to the callback can request these operations
+    /// to be performed on a worker thread owned by the stream.
     ///
     /// The `error_callback` will not be called because of actions by the application, such as stopping
     /// or closing a stream.
@@ -1181,35 +1409,326 @@ impl AAudioStreamBuilder {
     /// Note that the AAudio callbacks will never be called simultaneously from multiple threads.
     ///
     /// Available since API level 26.
-    pub fn set_callbacks<D, E>(mut self, data_callback: D, error_callback: E) -> Self
+    pub fn set_callbacks<D, E>(self, data_callback: D, error_callback: E) -> Self
     where
-        D: FnMut(&AAudioStreamInfo, &mut [u8], i32) -> CallbackResult + Send + 'static,
-        E: FnMut(&AAudioStreamInfo, Error) + Send + 'static,
+        D: FnMut(&StreamView<'_>, &mut [u8], i32) -> CallbackResult + Send + 'static,
+        E: FnMut(&StreamView<'_>, &StreamControl, Error) + Send + 'static,
     {
-        let data_callback: Box<
-            Box<dyn FnMut(&AAudioStreamInfo, &mut [u8], i32) -> CallbackResult + Send + 'static>,
-        > = Box::new(Box::new(data_callback));
-        let error_callback: Box<Box<dyn FnMut(&AAudioStreamInfo, Error) + Send + 'static>> =
-            Box::new(Box::new(error_callback));
-        let data_callback_raw = Box::into_raw(data_callback);
-        let error_callback_raw = Box::into_raw(error_callback);
-        let callbacks = StreamCallbacks {
-            _data_callback: unsafe { Box::from_raw(data_callback_raw) },
-            _error_callback: unsafe { Box::from_raw(error_callback_raw) },
-        };
+        let mut builder = self.set_error_callback(error_callback);
+        builder.callback_format = None;
+        builder.callback_channel_count = None;
+        builder.set_raw_data_callback(Box::new(data_callback))
+    }
+
+    /// Request that AAudio call the `data_callback` when the stream is running,
+    /// passing it the audio data as a slice of `F`.
+    ///
+    /// `F` is either a `Sample` type, eg. `f32` for interleaved samples of any channel count,
+    /// or a frame type, eg. `Stereo<f32>`, with one element of the slice per frame.
+    ///
+    /// This also requests the stream format matching the sample type, eg. `Format::F32` for `f32`
+    /// samples, and the channel count of the frame type. If the stream is opened with a different
+    /// format or channel count, `AAudioStreamBuilder::open_stream()` fails with
+    /// `ErrorKind::FormatMismatch` or `ErrorKind::ChannelCountMismatch`.
+    ///
+    /// The third argument of the callback is the number of frames in the buffer.
+    ///
+    /// If AAudio passes a buffer that is not aligned for `F`, the callback is not called.
+    /// The buffer is filled with silence, the stream is stopped and the error callback receives
+    /// an error of `ErrorKind::MisalignedBuffer`.
+    ///
+    /// See [`set_callbacks`] for the restrictions on the data callback.
+    ///
+    /// Available since API level 26.
+    ///
+    /// [`set_callbacks`]: AAudioStreamBuilder::set_callbacks
+    pub fn set_data_callback<F, D>(self, data_callback: D) -> Self
+    where
+        F: AudioFrame,
+        D: FnMut(&StreamView<'_>, &mut [F], i32) -> CallbackResult + Send + 'static,
+    {
+        let mut data_callback = data_callback;
+        let mut builder = self.set_frame_type::<F>();
+        builder.callback_format = Some(<F::Sample as Sample>::FORMAT);
+        builder.callback_channel_count = F::CHANNEL_COUNT;
+        let panic = PanicHandlerRef::new(&builder.callbacks.panic);
+        builder.set_raw_data_callback(Box::new(move |stream, data, num_frames| {
+            // AAudio buffers are allocated with the alignment of the stream format,
+            // and the format and channel count are checked to match `F` when the stream is opened.
+            let alignment = mem::align_of::<F>();
+            if data.as_ptr().align_offset(alignment) != 0 {
+                data.fill(0);
+                let kind = ErrorKind::MisalignedBuffer { alignment };
+                panic.get().report_error(Error::new(kind, "data_callback"));
+                return CallbackResult::Stop;
+            }
+            let frames = unsafe {
+                std::slice::from_raw_parts_mut(
+                    data.as_mut_ptr() as *mut F,
+                    data.len() / mem::size_of::<F>(),
+                )
+            };
+            data_callback(stream, frames, num_frames)
+        }))
+    }
+
+    /// Request that AAudio call the `error_callback` if any error occurs or the stream
+    /// is disconnected.
+    ///
+    /// See [`set_callbacks`] for the restrictions on the error callback.
+    ///
+    /// Available since API level 26.
+    ///
+    /// [`set_callbacks`]: AAudioStreamBuilder::set_callbacks
+    pub fn set_error_callback<E>(mut self, error_callback: E) -> Self
+    where
+        E: FnMut(&StreamView<'_>, &StreamControl, Error) + Send + 'static,
+    {
+        let handler = Box::new(ErrorHandler {
+            callback: Mutex::new(Box::new(error_callback)),
+            panic: &*self.callbacks.panic,
+            stream: OnceLock::new(),
+        });
+        let error_callback_raw = Box::into_raw(handler);
+        self.set_raw_error_callback(Some(raw_error_callback), error_callback_raw as *mut c_void);
+        self.callbacks._error_callback = Some(unsafe { Box::from_raw(error_callback_raw) });
+        self
+    }
+
+    fn set_raw_error_callback(&mut self, callback: ffi::ErrorCallback, user_data: *mut c_void) {
+        unsafe { ffi::AAudioStreamBuilder_setErrorCallback(self.raw, callback, user_data) }
+        self.error_callback = (callback, user_data);
+    }
+
+    /// Request that AAudio call the methods of `callback` when the stream is running and
+    /// if any error occurs or the stream is disconnected.
+    ///
+    /// `AudioStreamCallback::on_audio_ready()` is called as the data callback, see
+    /// [`set_callbacks`] for its restrictions.
+    ///
+    /// When an error occurs, the crate stops the stream on its worker thread and calls
+    /// `AudioStreamCallback::on_error_before_close()`. It then releases the audio resources
+    /// of the stream, if `AAudioStream::release()` is available, and calls
+    /// `AudioStreamCallback::on_error_after_close()`. The stream itself is closed once
+    /// the `AAudioStream` and all of its handles are dropped. While the error is handled,
+    /// the data callback stops the stream.
+    ///
+    /// This replaces the callbacks set with [`set_callbacks`], [`set_data_callback`],
+    /// [`set_error_callback`] and [`set_partial_data_callback`].
+    ///
+    /// Available since API level 26.
+    ///
+    /// [`set_callbacks`]: AAudioStreamBuilder::set_callbacks
+    /// [`set_data_callback`]: AAudioStreamBuilder::set_data_callback
+    /// [`set_error_callback`]: AAudioStreamBuilder::set_error_callback
+    /// [`set_partial_data_callback`]: AAudioStreamBuilder::set_partial_data_callback
+    pub fn set_callback<C>(mut self, callback: C) -> Self
+    where
+        C: AudioStreamCallback + 'static,
+    {
+        if self.callbacks._partial_data_callback.take().is_some() {
+            unsafe {
+                ffi::AAudioStreamBuilder_setPartialDataCallback(
+                    self.raw,
+                    None,
+                    std::ptr::null_mut(),
+                )
+            }
+        }
+        self.callback_format = None;
+        self.callback_channel_count = None;
+        let object = Box::new(CallbackObject::new(
+            Box::new(callback),
+            &*self.callbacks.panic,
+        ));
+        let object_raw = &*object as *const CallbackObject as *mut c_void;
+        unsafe {
+            ffi::AAudioStreamBuilder_setDataCallback(self.raw, Some(raw_callback_data), object_raw);
+        }
+        self.set_raw_error_callback(Some(raw_callback_error), object_raw);
+        self.callbacks._data_callback = None;
+        self.callbacks._error_callback = None;
+        self.callbacks._callback = Some(object);
+        self
+    }
+
+    /// Request that AAudio call the `presentation_end_callback` when all the data of an
+    /// offloaded stream that was written before `AAudioStream::set_offload_end_of_stream()`
+    /// has been played.
+    ///
+    /// This is only used for output streams with `PerformanceMode::PowerSavingOffloaded`.
+    ///
+    /// Available since API level 36.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    pub fn set_presentation_end_callback<P>(
+        self,
+        presentation_end_callback: P,
+    ) -> Result<Self, BuilderError>
+    where
+        P: FnMut(&StreamView<'_>) + Send + 'static,
+    {
+        let mut builder = self.require(
+            "AAudioStreamBuilder_setPresentationEndCallback",
+            "set_presentation_end_callback",
+        )?;
+        let callback: Box<PresentationEndCallback> = Box::new(presentation_end_callback);
+        let callback_raw = builder.handler(callback);
+        unsafe {
+            ffi::AAudioStreamBuilder_setPresentationEndCallback(
+                builder.raw,
+                Some(raw_presentation_end_callback),
+                callback_raw as *mut c_void,
+            );
+        }
+        builder.callbacks._presentation_end_callback = Some(unsafe { Box::from_raw(callback_raw) });
+        Ok(builder)
+    }
+
+    /// Request that AAudio call the `data_callback` when the stream is running and the
+    /// `error_callback` if any error occurs or the stream is disconnected.
+    ///
+    /// Unlike with [`set_callbacks`], the data callback may process fewer than the requested
+    /// number of frames, eg. if a decoder runs out of data. It returns the number of frames
+    /// it actually processed, between 0 and the number of frames of the buffer.
+    /// For an output stream, the frames that were not written are requested again
+    /// in the next callback. The stream is stopped if the callback returns a negative value
+    /// or a value greater than the number of frames of the buffer.
+    ///
+    /// This replaces the data callback set with [`set_callbacks`] or [`set_data_callback`].
+    /// See [`set_callbacks`] for the restrictions on the callbacks.
+    ///
+    /// Available since API level 36.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    ///
+    /// [`set_callbacks`]: AAudioStreamBuilder::set_callbacks
+    /// [`set_data_callback`]: AAudioStreamBuilder::set_data_callback
+    pub fn set_partial_data_callback<D, E>(
+        self,
+        data_callback: D,
+        error_callback: E,
+    ) -> Result<Self, BuilderError>
+    where
+        D: FnMut(&StreamView<'_>, &mut [u8], i32) -> i32 + Send + 'static,
+        E: FnMut(&StreamView<'_>, &StreamControl, Error) + Send + 'static,
+    {
+        let builder = self.require(
+            "AAudioStreamBuilder_setPartialDataCallback",
+            "set_partial_data_callback",
+        )?;
+        let mut builder = builder.set_error_callback(error_callback);
+        builder.callback_format = None;
+        builder.callback_channel_count = None;
+        let data_callback_set = builder.callbacks._data_callback.take().is_some();
+        if data_callback_set || builder.callbacks._callback.take().is_some() {
+            unsafe {
+                ffi::AAudioStreamBuilder_setDataCallback(builder.raw, None, std::ptr::null_mut())
+            }
+        }
+        let data_callback: Box<PartialDataCallback> = Box::new(data_callback);
+        let data_callback_raw = builder.handler(data_callback);
+        unsafe {
+            ffi::AAudioStreamBuilder_setPartialDataCallback(
+                builder.raw,
+                Some(raw_partial_data_callback),
+                data_callback_raw as *mut c_void,
+            );
+        }
+        builder.callbacks._partial_data_callback =
+            Some(unsafe { Box::from_raw(data_callback_raw) });
+        Ok(builder)
+    }
+
+    /// Returns a new handler of `callback`, to be passed to AAudio and then owned
+    /// by the callbacks of the builder.
+    fn handler<C: ?Sized>(&self, callback: Box<C>) -> *mut Handler<C> {
+        Box::into_raw(Box::new(Handler {
+            callback,
+            panic: &*self.callbacks.panic,
+        }))
+    }
+
+    /// Sets what to do when a callback panics. The default is `PanicPolicy::Abort`.
+    ///
+    /// Panics of the callbacks are reported on the worker thread of the stream to the hook
+    /// set with [`set_panic_hook`] or, without a hook, to the error callback as an error
+    /// of `ErrorKind::Panicked`, whose operation names the callback that panicked.
+    /// Panics of the error callback itself are not passed to it. Without a hook or an error
+    /// callback, the payloads are kept until taken with `AAudioStream::take_panic()`.
+    /// With `PanicPolicy::Abort`, the process is aborted once the panic is reported,
+    /// or after a second if the worker thread is busy.
+    ///
+    /// [`set_panic_hook`]: AAudioStreamBuilder::set_panic_hook
+    pub fn set_panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.callbacks.panic.policy = policy;
+        self
+    }
+
+    /// Sets the function receiving the payload of panics of the callbacks,
+    /// instead of the error callback.
+    ///
+    /// Without an error callback, the hook also receives the errors that the crate detects
+    /// while running the callbacks, with an `Error` as payload, eg. when a stream could not
+    /// be stopped after an error reported to an `AudioStreamCallback`.
+    ///
+    /// See [`set_panic_policy`].
+    ///
+    /// [`set_panic_policy`]: AAudioStreamBuilder::set_panic_policy
+    pub fn set_panic_hook<H>(mut self, hook: H) -> Self
+    where
+        H: FnMut(Box<dyn Any + Send>) + Send + 'static,
+    {
+        self.callbacks.panic.hook = Some(Mutex::new(Box::new(hook)));
+        self
+    }
+
+    fn set_raw_data_callback(mut self, data_callback: Box<DataCallback>) -> Self {
+        // Only one of the data callbacks may be set.
+        if self.callbacks._partial_data_callback.take().is_some() {
+            unsafe {
+                ffi::AAudioStreamBuilder_setPartialDataCallback(
+                    self.raw,
+                    None,
+                    std::ptr::null_mut(),
+                )
+            }
+        }
+        let data_callback_raw = self.handler(data_callback);
         unsafe {
             ffi::AAudioStreamBuilder_setDataCallback(
                 self.raw,
                 Some(raw_data_callback),
                 data_callback_raw as *mut c_void,
             );
-            ffi::AAudioStreamBuilder_setErrorCallback(
-                self.raw,
-                Some(raw_error_callback),
-                error_callback_raw as *mut c_void,
-            );
         }
-        self.callbacks = Some(callbacks);
+        self.callbacks._data_callback = Some(unsafe { Box::from_raw(data_callback_raw) });
+        self
+    }
+
+    /// Request that the stream be reopened and restarted when its device is disconnected,
+    /// eg. when headphones are unplugged.
+    ///
+    /// When AAudio reports `ResultCode::Disconnected`, the crate stops the stream
+    /// on its worker thread, reopens it with the configuration and the callbacks of this builder
+    /// on the default device once the callbacks of the disconnected stream have returned,
+    /// and starts it. The `AAudioStream` and its handles then refer to the new stream,
+    /// and `route_change_callback` is called with it on the worker thread. Its panics are
+    /// handled according to [`set_panic_policy`].
+    /// The audio resources of the disconnected stream are released if `AAudioStream::release()`
+    /// is available, and the stream is closed on the next restart or together with the new one.
+    ///
+    /// The error callback is not called for the disconnect. It receives the error of the restart
+    /// instead if the stream cannot be reopened or started, eg. `ErrorKind::FormatMismatch`
+    /// or `ErrorKind::ChannelCountMismatch` if it is reopened with a different format
+    /// or channel count. It is still called for other errors.
+    ///
+    /// Available since API level 26.
+    ///
+    /// [`set_panic_policy`]: AAudioStreamBuilder::set_panic_policy
+    pub fn set_auto_restart<R>(mut self, route_change_callback: R) -> Self
+    where
+        R: FnMut(&StreamView<'_>) + Send + 'static,
+    {
+        self.route_change_callback = Some(Box::new(route_change_callback));
         self
     }
 
@@ -1263,18 +1782,53 @@ impl AAudioStreamBuilder {
     /// If an exact value is specified then an opened stream will use that value.
     /// If a stream cannot be opened with the specified value then the open will fail.
     ///
+    /// If both this function and `AAudioStreamBuilder::set_channel_mask()` are called,
+    /// the last called function will be respected.
+    ///
     /// Available since API level 26.
     ///
     /// # Arguments
     ///
     /// * `channel_count` - Number of channels desired.
-    pub fn set_channel_count(self, channel_count: i32) -> Self {
+    pub fn set_channel_count(mut self, channel_count: i32) -> Self {
         unsafe {
             ffi::AAudioStreamBuilder_setChannelCount(self.raw, channel_count);
         }
+        self.channel_mask = None;
         self
     }
 
+    /// Set audio channel mask for the stream.
+    ///
+    /// The default, if you do not call this function, is unspecified.
+    /// If both channel mask and count are not set, then stereo will then be chosen when the
+    /// stream is opened.
+    /// After opening a stream with an unspecified value, the application must query for the
+    /// actual value, which may vary by device.
+    ///
+    /// If an exact value is specified then an opened stream will use that value.
+    /// If a stream cannot be opened with the specified value then the open will fail.
+    ///
+    /// The channel count of the stream is the number of channels in the mask. If both this
+    /// function and `AAudioStreamBuilder::set_channel_count()` are called, the last called
+    /// function will be respected. Frame types of typed callbacks and streams keep the mask
+    /// if it has as many channels as the frame type.
+    ///
+    /// Available since API level 32.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    ///
+    /// # Arguments
+    ///
+    /// * `channel_mask` - Audio channel mask desired.
+    pub fn set_channel_mask(self, channel_mask: ChannelMask) -> Result<Self, BuilderError> {
+        let mut builder = self.require("AAudioStreamBuilder_setChannelMask", "set_channel_mask")?;
+        unsafe {
+            ffi::AAudioStreamBuilder_setChannelMask(builder.raw, channel_mask.bits());
+        }
+        builder.channel_mask = Some(channel_mask);
+        Ok(builder)
+    }
+
     /// Request a sample data format, for example `Format::I16`.
     ///
     /// The default, if you do not call this function, is `Unspecified`.
@@ -1291,7 +1845,7 @@ impl AAudioStreamBuilder {
     ///
     /// * `format` - the sample data format.
     pub fn set_format(self, format: Format) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setFormat(self.raw, format as i32) }
+        unsafe { ffi::AAudioStreamBuilder_setFormat(self.raw, format.into()) }
         self
     }
 
@@ -1308,7 +1862,7 @@ impl AAudioStreamBuilder {
     ///
     /// * `sharing_mode` - `SharingMode::Shared` or `SharingMode::Exclusive`
     pub fn set_sharing_mode(self, sharing_mode: SharingMode) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setSharingMode(self.raw, sharing_mode as i32) }
+        unsafe { ffi::AAudioStreamBuilder_setSharingMode(self.raw, sharing_mode.into()) }
         self
     }
 
@@ -1322,7 +1876,7 @@ impl AAudioStreamBuilder {
     ///
     /// * `direction` - `Direction::Output` or `Direction::Input`
     pub fn set_direction(self, direction: Direction) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setDirection(self.raw, direction as i32) }
+        unsafe { ffi::AAudioStreamBuilder_setDirection(self.raw, direction.into()) }
         self
     }
 
@@ -1337,7 +1891,7 @@ impl AAudioStreamBuilder {
     ///
     /// * `num_frames` - the desired buffer capacity in frames or 0 for unspecified
     pub fn set_buffer_capacity_in_frames(self, num_frames: i32) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setBufferCapacityInFrames(self.raw, num_frames as i32) }
+        unsafe { ffi::AAudioStreamBuilder_setBufferCapacityInFrames(self.raw, num_frames) }
         self
     }
 
@@ -1348,7 +1902,7 @@ impl AAudioStreamBuilder {
     /// The default, if you do not call this function, is None.
     ///
     /// You may not get the mode you requested.
-    /// You can call `AAudioStream::get_performance_mode()`
+    /// You can call `StreamProperties::get_performance_mode()`
     /// to find out the final mode for the stream.
     ///
     /// Available since API level 26.
@@ -1357,7 +1911,7 @@ impl AAudioStreamBuilder {
     ///
     /// * `mode` - the desired performance mode, eg. LowLatency
     pub fn set_performance_mode(self, mode: PerformanceMode) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setPerformanceMode(self.raw, mode as i32) }
+        unsafe { ffi::AAudioStreamBuilder_setPerformanceMode(self.raw, mode.into()) }
         self
     }
 
@@ -1370,11 +1924,13 @@ impl AAudioStreamBuilder {
     /// The default, if you do not call this function, is `Usage::Media`.
     ///
     /// Available since API level 28.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
     ///
     /// * `usage` - the desired usage, eg. `Usage::Game`
-    pub fn set_usage(self, usage: Usage) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setUsage(self.raw, usage as i32) }
-        self
+    pub fn set_usage(self, usage: Usage) -> Result<Self, BuilderError> {
+        let builder = self.require("AAudioStreamBuilder_setUsage", "set_usage")?;
+        unsafe { ffi::AAudioStreamBuilder_setUsage(builder.raw, usage.into()) }
+        Ok(builder)
     }
 
     /// Set the type of audio data that the stream will carry.
@@ -1386,13 +1942,15 @@ impl AAudioStreamBuilder {
     /// The default, if you do not call this function, is `ContentType::Music`.
     ///
     /// Available since API level 28.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
     ///
     /// # Arguments
     ///
     /// * `content_type` - the type of audio data, eg. `ContentType::Speech`
-    pub fn set_content_type(self, content_type: ContentType) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setContentType(self.raw, content_type as i32) }
-        self
+    pub fn set_content_type(self, content_type: ContentType) -> Result<Self, BuilderError> {
+        let builder = self.require("AAudioStreamBuilder_setContentType", "set_content_type")?;
+        unsafe { ffi::AAudioStreamBuilder_setContentType(builder.raw, content_type.into()) }
+        Ok(builder)
     }
 
     /// Set the input (capture) preset for the stream.
@@ -1407,13 +1965,15 @@ impl AAudioStreamBuilder {
     /// on many platforms.
     ///
     /// Available since API level 28.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
     ///
     /// # Arguments
     ///
     /// * `input_preset` - the desired configuration for recording
-    pub fn set_input_preset(self, input_preset: InputPreset) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setInputPreset(self.raw, input_preset as i32) }
-        self
+    pub fn set_input_preset(self, input_preset: InputPreset) -> Result<Self, BuilderError> {
+        let builder = self.require("AAudioStreamBuilder_setInputPreset", "set_input_preset")?;
+        unsafe { ffi::AAudioStreamBuilder_setInputPreset(builder.raw, input_preset.into()) }
+        Ok(builder)
     }
 
     /// Specify whether this stream audio may or may not be captured by other apps or the system.
@@ -1424,25 +1984,39 @@ impl AAudioStreamBuilder {
     /// policy is always applied. See android.media.AudioAttributes#setAllowedCapturePolicy(int)
     ///
     /// Available since API level 29.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
     ///
     /// # Arguments
     ///
     /// * `policy` - the desired level of opt-out from being captured.
-    pub fn set_allowed_capture_policy(self, policy: AllowedCapturePolicy) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setAllowedCapturePolicy(self.raw, policy as i32) }
-        self
+    pub fn set_allowed_capture_policy(
+        self,
+        policy: AllowedCapturePolicy,
+    ) -> Result<Self, BuilderError> {
+        let builder = self.require(
+            "AAudioStreamBuilder_setAllowedCapturePolicy",
+            "set_allowed_capture_policy",
+        )?;
+        unsafe { ffi::AAudioStreamBuilder_setAllowedCapturePolicy(builder.raw, policy.into()) }
+        Ok(builder)
     }
 
     /// Equivalent to invoking `AAudioStreamBuilder::set_session_id` with 0 argument.
-    pub fn allocate_session_id(self) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setSessionId(self.raw, 0) }
-        self
+    ///
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    pub fn allocate_session_id(self) -> Result<Self, BuilderError> {
+        let builder = self.require("AAudioStreamBuilder_setSessionId", "allocate_session_id")?;
+        unsafe { ffi::AAudioStreamBuilder_setSessionId(builder.raw, 0) }
+        Ok(builder)
     }
 
     /// Equivalent to invoking `AAudioStreamBuilder::set_session_id` with -1 argument.
-    pub fn remove_session_id(self) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setSessionId(self.raw, -1) }
-        self
+    ///
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    pub fn remove_session_id(self) -> Result<Self, BuilderError> {
+        let builder = self.require("AAudioStreamBuilder_setSessionId", "remove_session_id")?;
+        unsafe { ffi::AAudioStreamBuilder_setSessionId(builder.raw, -1) }
+        Ok(builder)
     }
 
     /// The session ID can be used to associate a stream with effects processors.
@@ -1453,7 +2027,7 @@ impl AAudioStreamBuilder {
     /// If set to 0 then a session ID will be allocated
     /// when the stream is opened.
     ///
-    /// The allocated session ID can be obtained by calling `AAudioStream::get_session_id()`
+    /// The allocated session ID can be obtained by calling `StreamProperties::get_session_id()`
     /// and then used with this function when opening another stream.
     /// This allows effects to be shared between streams.
     ///
@@ -1466,13 +2040,15 @@ impl AAudioStreamBuilder {
     /// Allocated session IDs will always be positive and nonzero.
     ///
     /// Available since API level 28.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
     ///
     /// # Arguments
     ///
     /// * `session_id` - an allocated sessionID or 0 to allocate a new sessionID
-    pub fn set_session_id(self, session_id: i32) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setSessionId(self.raw, session_id as i32) }
-        self
+    pub fn set_session_id(self, session_id: i32) -> Result<Self, BuilderError> {
+        let builder = self.require("AAudioStreamBuilder_setSessionId", "set_session_id")?;
+        unsafe { ffi::AAudioStreamBuilder_setSessionId(builder.raw, session_id) }
+        Ok(builder)
     }
 
     /// Indicates whether this input stream must be marked as privacy sensitive or not.
@@ -1488,13 +2064,123 @@ impl AAudioStreamBuilder {
     /// Only relevant if the stream direction is `Direction::Input`.
     ///
     /// Added in API level 30.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
     ///
     /// # Arguments
     ///
     /// * `privacy_sensitive` - `true` if capture from this stream must be marked as privacy sensitive, `false` otherwise.
-    pub fn set_privacy_sensitive(self, privacy_sensitive: bool) -> Self {
-        unsafe { ffi::AAudioStreamBuilder_setPrivacySensitive(self.raw, privacy_sensitive) }
-        self
+    pub fn set_privacy_sensitive(self, privacy_sensitive: bool) -> Result<Self, BuilderError> {
+        let builder = self.require(
+            "AAudioStreamBuilder_setPrivacySensitive",
+            "set_privacy_sensitive",
+        )?;
+        unsafe { ffi::AAudioStreamBuilder_setPrivacySensitive(builder.raw, privacy_sensitive) }
+        Ok(builder)
+    }
+
+    /// Sets the behavior affecting whether spatialization will be used.
+    ///
+    /// The AAudio system will use this information to select whether the stream will go
+    /// through a spatializer effect or not when the effect is supported and enabled.
+    ///
+    /// The default is `SpatializationBehavior::Auto`.
+    ///
+    /// Available since API level 32.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    ///
+    /// # Arguments
+    ///
+    /// * `behavior` - the desired behavior with regards to spatialization.
+    pub fn set_spatialization_behavior(
+        self,
+        behavior: SpatializationBehavior,
+    ) -> Result<Self, BuilderError> {
+        let builder = self.require(
+            "AAudioStreamBuilder_setSpatializationBehavior",
+            "set_spatialization_behavior",
+        )?;
+        unsafe { ffi::AAudioStreamBuilder_setSpatializationBehavior(builder.raw, behavior.into()) }
+        Ok(builder)
+    }
+
+    /// Specifies whether the audio data of this output stream has already been processed for
+    /// spatialization.
+    ///
+    /// If the stream has been processed for spatialization, setting this to `true` will prevent
+    /// issues such as double-processing on platforms that will spatialize audio data.
+    ///
+    /// The default is `false`.
+    ///
+    /// Available since API level 32.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_spatialized` - `true` if the content is already processed for binaural or
+    ///   transaural spatial rendering, `false` otherwise.
+    pub fn set_content_spatialized(self, is_spatialized: bool) -> Result<Self, BuilderError> {
+        let builder = self.require(
+            "AAudioStreamBuilder_setIsContentSpatialized",
+            "set_content_spatialized",
+        )?;
+        unsafe { ffi::AAudioStreamBuilder_setIsContentSpatialized(builder.raw, is_spatialized) }
+        Ok(builder)
+    }
+
+    /// Declare the name of the package creating the stream.
+    ///
+    /// This is usually Context#getPackageName().
+    ///
+    /// The default, if you do not call this function, is a random package in the calling uid.
+    /// The vast majority of apps have only one package per calling UID.
+    /// If an invalid package name is set, input streams may not be given permission to
+    /// record when started.
+    ///
+    /// The package name is usually the applicationId in your app's build.gradle file.
+    ///
+    /// Available since API level 31.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device,
+    /// or with `ResultCode::IllegalArgument` if `package_name` contains a nul byte.
+    ///
+    /// # Arguments
+    ///
+    /// * `package_name` - packageName of the calling app.
+    pub fn set_package_name(self, package_name: &str) -> Result<Self, BuilderError> {
+        let mut builder = self.require("AAudioStreamBuilder_setPackageName", "set_package_name")?;
+        let package_name = match to_c_string(package_name, "set_package_name") {
+            Ok(package_name) => package_name,
+            Err(error) => return Err(BuilderError::new(builder, error)),
+        };
+        unsafe { ffi::AAudioStreamBuilder_setPackageName(builder.raw, package_name.as_ptr()) }
+        builder.package_name = Some(package_name);
+        Ok(builder)
+    }
+
+    /// Declare the attribution tag of the context creating the stream.
+    ///
+    /// This is usually Context#getAttributionTag().
+    ///
+    /// The default, if you do not call this function, is no attribution tag.
+    ///
+    /// Available since API level 31.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device,
+    /// or with `ResultCode::IllegalArgument` if `attribution_tag` contains a nul byte.
+    ///
+    /// # Arguments
+    ///
+    /// * `attribution_tag` - attributionTag of the calling context.
+    pub fn set_attribution_tag(self, attribution_tag: &str) -> Result<Self, BuilderError> {
+        let mut builder = self.require(
+            "AAudioStreamBuilder_setAttributionTag",
+            "set_attribution_tag",
+        )?;
+        let attribution_tag = match to_c_string(attribution_tag, "set_attribution_tag") {
+            Ok(attribution_tag) => attribution_tag,
+            Err(error) => return Err(BuilderError::new(builder, error)),
+        };
+        unsafe { ffi::AAudioStreamBuilder_setAttributionTag(builder.raw, attribution_tag.as_ptr()) }
+        builder.attribution_tag = Some(attribution_tag);
+        Ok(builder)
     }
 
     /// Set the requested data callback buffer size in frames.
@@ -1528,21 +2214,150 @@ impl AAudioStreamBuilder {
 
     /// Open a stream based on the options in the AAudioStreamBuilder.
     pub fn open_stream(mut self) -> Result<AAudioStream, Error> {
+        let (error_callback, error_user_data) = self.error_callback;
+        let restart = self.route_change_callback.take().map(|callback| {
+            Box::new(Restart::new(
+                error_callback,
+                error_user_data,
+                callback,
+                &*self.callbacks.panic,
+            ))
+        });
+        if let Some(restart) = &restart {
+            let restart_raw = &**restart as *const Restart as *mut c_void;
+            unsafe {
+                ffi::AAudioStreamBuilder_setErrorCallback(
+                    self.raw,
+                    Some(raw_restart_error_callback),
+                    restart_raw,
+                )
+            }
+        }
         let mut raw = MaybeUninit::<*mut AAudioStreamRaw>::uninit();
         let result = unsafe { ffi::AAudioStreamBuilder_openStream(self.raw, raw.as_mut_ptr()) };
-        wrap_result(result)?;
+        wrap_result(result, "open_stream")?;
+        let raw = unsafe { raw.assume_init() };
+        // The builder is kept to reopen the stream.
+        let builder = mem::replace(&mut self.raw, std::ptr::null_mut());
+        let strings = self
+            .package_name
+            .take()
+            .into_iter()
+            .chain(self.attribution_tag.take())
+            .collect();
         let stream = AAudioStream {
-            raw: unsafe { raw.assume_init() },
-            _callbacks: self.callbacks.take(),
+            inner: StreamInner::new(
+                raw,
+                builder,
+                strings,
+                mem::take(&mut self.callbacks),
+                restart,
+            ),
         };
+        if let Some(requested) = self.callback_format {
+            let granted = stream.get_format();
+            if granted != requested {
+                return Err(stream.error(
+                    ErrorKind::FormatMismatch { requested, granted },
+                    "open_stream",
+                ));
+            }
+        }
+        if let Some(requested) = self.callback_channel_count {
+            let granted = stream.get_channel_count();
+            if granted != requested {
+                return Err(stream.error(
+                    ErrorKind::ChannelCountMismatch { requested, granted },
+                    "open_stream",
+                ));
+            }
+        }
         Ok(stream)
     }
+
+    /// Open an output stream that plays frames of type `F`.
+    ///
+    /// This sets the direction to `Direction::Output`, the format matching the sample type
+    /// of `F` and, for frame types, their channel count. Fails with `ErrorKind::FormatMismatch`
+    /// or `ErrorKind::ChannelCountMismatch` if the stream is opened with a different format
+    /// or channel count.
+    pub fn open_output<F: AudioFrame>(self) -> Result<OutputStream<F>, Error> {
+        let stream = self
+            .set_direction(Direction::Output)
+            .set_frame_type::<F>()
+            .open_stream()?;
+        stream.check_frame_type::<F>("open_output")?;
+        Ok(OutputStream::new(stream))
+    }
+
+    /// Open an input stream that records frames of type `F`.
+    ///
+    /// This sets the direction to `Direction::Input`, the format matching the sample type
+    /// of `F` and, for frame types, their channel count. Fails with `ErrorKind::FormatMismatch`
+    /// or `ErrorKind::ChannelCountMismatch` if the stream is opened with a different format
+    /// or channel count.
+    pub fn open_input<F: AudioFrame>(self) -> Result<InputStream<F>, Error> {
+        let stream = self
+            .set_direction(Direction::Input)
+            .set_frame_type::<F>()
+            .open_stream()?;
+        stream.check_frame_type::<F>("open_input")?;
+        Ok(InputStream::new(stream))
+    }
+
+    /// Open an output stream that plays interleaved samples of type `T` pushed to a ring buffer.
+    /// Returns the stream together with the producer half of the ring buffer.
+    ///
+    /// The samples can be pushed from any thread, while the data callback of the stream pops
+    /// them without blocking nor allocating. The callback pops whole frames only and fills
+    /// the rest of its buffer with silence when the ring buffer runs out of samples.
+    /// The underruns and overruns of the ring buffer are counted by
+    /// `RingBufferProducer::get_underrun_count()` and `RingBufferProducer::get_overrun_count()`.
+    ///
+    /// This sets the direction to `Direction::Output` and a data callback, see
+    /// `AAudioStreamBuilder::set_data_callback()`. Fails with `ErrorKind::FormatMismatch`
+    /// if the stream is opened with a different format, and with `ResultCode::IllegalArgument`
+    /// if `capacity` is too large, see `ring_buffer()`.
+    ///
+    /// Available since API level 26.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The number of samples the ring buffer can hold, rounded up to a power of two.
+    pub fn open_stream_with_ring_buffer<T: Sample>(
+        self,
+        capacity: usize,
+    ) -> Result<(AAudioStream, RingBufferProducer<T>), Error> {
+        let (producer, mut consumer) = ring_buffer::<T>(capacity)?;
+        let stream = self
+            .set_direction(Direction::Output)
+            .set_data_callback::<T, _>(move |stream, data, _| {
+                consumer.pop_frames_or_silence(data, stream.get_channel_count() as usize);
+                CallbackResult::Continue
+            })
+            .open_stream()?;
+        Ok((stream, producer))
+    }
+
+    fn set_frame_type<F: AudioFrame>(self) -> Self {
+        let builder = self.set_format(<F::Sample as Sample>::FORMAT);
+        let mask_channel_count = builder.channel_mask.map(ChannelMask::channel_count);
+        match F::CHANNEL_COUNT {
+            Some(channel_count) if mask_channel_count != Some(channel_count) => {
+                builder.set_channel_count(channel_count)
+            }
+            _ => builder,
+        }
+    }
 }
 
 impl Drop for AAudioStreamBuilder {
     fn drop(&mut self) {
-        unsafe {
-            ffi::AAudioStreamBuilder_delete(self.raw);
+        // The builder is owned by the stream once it is opened.
+        if !self.raw.is_null() {
+            unsafe {
+                ffi::AAudioStreamBuilder_delete(self.raw);
+            }
         }
     }
 }
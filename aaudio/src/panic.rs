@@ -0,0 +1,233 @@
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Mutex, OnceLock, PoisonError, Weak};
+use std::time::Duration;
+
+use crate::control::Worker;
+use crate::handle::StreamInner;
+use crate::{CallbackResult, Error, ErrorKind};
+
+/// What to do when a callback of a stream panics, set with
+/// `AAudioStreamBuilder::set_panic_policy()`.
+///
+/// Unwinding into AAudio is undefined behavior, so panics never leave the callbacks.
+/// A data callback that panicked is not called again. Instead, the stream is given silence
+/// for output streams, and the recorded data is dropped for input streams.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+#[non_exhaustive]
+pub enum PanicPolicy {
+    /// Abort the process. This is the default.
+    #[default]
+    Abort,
+
+    /// Output silence and stop the stream by returning `CallbackResult::Stop`.
+    Stop,
+
+    /// Keep the stream running and output silence.
+    Silence,
+}
+
+/// How long a callback that panicked with `PanicPolicy::Abort` waits for the panic
+/// to be reported before aborting the process.
+const ABORT_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub(crate) type PanicHook = dyn FnMut(Box<dyn Any + Send>) + Send + 'static;
+
+thread_local! {
+    /// Set while a callback of a stream runs on the current thread.
+    static IN_CALLBACK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns `true` if called from a callback of a stream.
+pub(crate) fn in_callback() -> bool {
+    IN_CALLBACK.with(Cell::get)
+}
+
+/// Catches the panics of the callbacks of a stream and reports them.
+#[derive(Default)]
+pub(crate) struct PanicHandler {
+    pub(crate) policy: PanicPolicy,
+    pub(crate) hook: Option<Mutex<Box<PanicHook>>>,
+    /// Set once a data callback panics.
+    panicked: AtomicBool,
+    /// The stream the callbacks are registered for and its worker, set once it is opened.
+    stream: OnceLock<(Weak<StreamInner>, Worker)>,
+    /// The payloads reported without a hook or an error callback to receive them.
+    unreported: Mutex<VecDeque<Box<dyn Any + Send>>>,
+}
+
+impl PanicHandler {
+    pub(crate) fn attach(&self, stream: Weak<StreamInner>, worker: Worker) {
+        let _ = self.stream.set((stream, worker));
+    }
+
+    /// Runs the body of a data callback. Returns `None` if it panics or has panicked before.
+    pub(crate) fn catch_data<R, F: FnOnce() -> R>(&self, body: F) -> Option<R> {
+        if self.panicked.load(Ordering::Acquire) {
+            return None;
+        }
+        let result = self.catch("data_callback", body);
+        if result.is_none() {
+            self.panicked.store(true, Ordering::Release);
+        }
+        result
+    }
+
+    /// Runs the body of the callback `operation`, other than the data callback.
+    /// Returns `None` if it panics.
+    pub(crate) fn catch_other<R, F: FnOnce() -> R>(
+        &self,
+        operation: &'static str,
+        body: F,
+    ) -> Option<R> {
+        self.catch(operation, body)
+    }
+
+    /// Fills the buffer of a data callback that panicked with silence and returns
+    /// the result of the callback.
+    pub(crate) fn silence(&self, data: &mut [u8]) -> CallbackResult {
+        data.fill(0);
+        match self.policy {
+            PanicPolicy::Silence => CallbackResult::Continue,
+            _ => CallbackResult::Stop,
+        }
+    }
+
+    /// Returns the oldest payload that could not be reported.
+    pub(crate) fn take_unreported(&self) -> Option<Box<dyn Any + Send>> {
+        self.unreported
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop_front()
+    }
+
+    fn catch<R, F: FnOnce() -> R>(&self, operation: &'static str, body: F) -> Option<R> {
+        let nested = IN_CALLBACK.with(|flag| flag.replace(true));
+        // A callback that panicked is never called again, so it cannot be observed
+        // in a broken state.
+        let result = panic::catch_unwind(AssertUnwindSafe(body));
+        IN_CALLBACK.with(|flag| flag.set(nested));
+        match result {
+            Ok(result) => Some(result),
+            Err(payload) => {
+                self.report(payload, operation);
+                None
+            }
+        }
+    }
+
+    /// Passes `error`, detected by the crate in a callback, to the error callback or,
+    /// without an error callback, to the hook. Without either, the error is kept
+    /// as payload until taken.
+    ///
+    /// The error is reported on the worker thread of the stream, since callbacks must not block.
+    pub(crate) fn report_error(&self, error: Error) {
+        let (stream, worker) = match self.stream.get() {
+            Some((stream, worker)) => (stream.clone(), worker),
+            None => return,
+        };
+        worker.run(move || {
+            let inner = match stream.upgrade() {
+                Some(inner) => inner,
+                None => return,
+            };
+            let error = error.with_stream(inner.raw());
+            match inner.error_handler() {
+                // The stream is kept open by `inner` during the call.
+                Some(handler) => unsafe { handler.call(inner.raw(), error) },
+                None => inner.panic_handler().deliver(Box::new(error)),
+            }
+        });
+    }
+
+    /// Passes the `payload` of a panic of the callback `operation` to the hook or,
+    /// without a hook, to the error callback as an error of `ErrorKind::Panicked`.
+    /// Panics of the error callback itself are not passed to it. Without either,
+    /// the payload is kept until taken.
+    ///
+    /// The panic is reported on the worker thread of the stream, since callbacks must not block.
+    /// With `PanicPolicy::Abort`, the process is aborted once the panic is reported,
+    /// or after `ABORT_TIMEOUT` if the worker thread is busy.
+    fn report(&self, payload: Box<dyn Any + Send>, operation: &'static str) {
+        let abort = self.policy == PanicPolicy::Abort;
+        let (stream, worker) = match self.stream.get() {
+            Some((stream, worker)) => (stream.clone(), worker),
+            None if abort => process::abort(),
+            None => return,
+        };
+        let (done, reported) = mpsc::channel();
+        let job = move || {
+            if let Some(inner) = stream.upgrade() {
+                let panic = inner.panic_handler();
+                match inner.error_handler() {
+                    Some(handler) if panic.hook.is_none() && operation != "error_callback" => {
+                        let error = Error::new(ErrorKind::Panicked, operation)
+                            .with_panic_message(panic_message(&*payload))
+                            .with_stream(inner.raw());
+                        // The stream is kept open by `inner` during the call.
+                        unsafe { handler.call(inner.raw(), error) };
+                    }
+                    _ => panic.deliver(payload),
+                }
+            }
+            let _ = done.send(());
+        };
+        if !abort {
+            worker.run(job);
+            return;
+        }
+        // A callback run by the worker thread cannot wait for it.
+        if worker.is_current() {
+            job();
+        } else {
+            worker.run(job);
+            let _ = reported.recv_timeout(ABORT_TIMEOUT);
+        }
+        process::abort();
+    }
+
+    /// Passes `payload` to the hook or, without a hook, keeps it until taken.
+    fn deliver(&self, payload: Box<dyn Any + Send>) {
+        match &self.hook {
+            Some(hook) => (hook.lock().unwrap_or_else(PoisonError::into_inner))(payload),
+            None => self
+                .unreported
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push_back(payload),
+        }
+    }
+}
+
+/// A pointer to the panic handler of a stream, moved into the callbacks that the crate
+/// wraps around those of the application.
+pub(crate) struct PanicHandlerRef(*const PanicHandler);
+
+// The handler is owned by `StreamCallbacks` and dropped after the callbacks.
+unsafe impl Send for PanicHandlerRef {}
+
+impl PanicHandlerRef {
+    pub(crate) fn new(handler: &PanicHandler) -> Self {
+        Self(handler)
+    }
+
+    pub(crate) fn get(&self) -> &PanicHandler {
+        unsafe { &*self.0 }
+    }
+}
+
+/// Returns the message of a panic, if it is a string.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "Box<dyn Any>".to_string(),
+        },
+    }
+}
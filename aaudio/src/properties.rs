@@ -0,0 +1,451 @@
+use std::mem::MaybeUninit;
+
+use crate::{
+    ffi, require, wrap_stream_result, AAudioStream, AAudioStreamRaw, AllowedCapturePolicy,
+    ChannelMask, Clock, ContentType, Direction, Error, Format, HardwareInfo, InputPreset,
+    PerformanceMode, SharingMode, SpatializationBehavior, StreamConfig, StreamState, StreamView,
+    Timestamp, Usage,
+};
+
+pub(crate) mod private {
+    pub trait Sealed {
+        /// Returns the AAudio stream the properties are read from.
+        fn raw(&self) -> *mut crate::AAudioStreamRaw;
+    }
+
+    impl Sealed for crate::AAudioStream {
+        fn raw(&self) -> *mut crate::AAudioStreamRaw {
+            self.inner.raw()
+        }
+    }
+
+    impl<'a> Sealed for crate::StreamView<'a> {
+        fn raw(&self) -> *mut crate::AAudioStreamRaw {
+            self.raw
+        }
+    }
+}
+
+/// The getters of a stream, shared by the owning `AAudioStream`, the `StreamView`
+/// passed to callbacks, the `StreamHandle` and the typed `OutputStream` and `InputStream`.
+///
+/// Generic code can accept any of them with a `StreamProperties` bound.
+///
+/// This trait is sealed.
+pub trait StreamProperties: private::Sealed {
+    /// Returns the actual sample rate.
+    ///
+    /// Available since API level 26.
+    fn get_sample_rate(&self) -> i32 {
+        unsafe { ffi::AAudioStream_getSampleRate(self.raw()) }
+    }
+
+    /// A stream has one or more channels of data.
+    /// A frame will contain one sample for each channel.
+    ///
+    /// Available since API level 26.
+    fn get_channel_count(&self) -> i32 {
+        unsafe { ffi::AAudioStream_getChannelCount(self.raw()) }
+    }
+
+    /// Return the channel mask for the stream. This will be the mask set using
+    /// `AAudioStreamBuilder::set_channel_mask()`, or `ChannelMask::UNSPECIFIED` otherwise.
+    ///
+    /// If the mask is specified, `get_channel_count()` returns the number of its channels.
+    ///
+    /// Available since API level 32.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    fn get_channel_mask(&self) -> Result<ChannelMask, Error> {
+        require("AAudioStream_getChannelMask", "get_channel_mask")?;
+        Ok(ChannelMask::from_bits(unsafe {
+            ffi::AAudioStream_getChannelMask(self.raw())
+        }))
+    }
+
+    /// Query the maximum number of frames that can be filled without blocking.
+    ///
+    /// Available since API level 26.
+    fn get_buffer_size_in_frames(&self) -> i32 {
+        unsafe { ffi::AAudioStream_getBufferSizeInFrames(self.raw()) }
+    }
+
+    /// Query the number of frames that the application should read or write at
+    /// one time for optimal performance. It is OK if an application writes
+    /// a different number of frames. But the buffer size may need to be larger
+    /// in order to avoid underruns or overruns.
+    ///
+    /// Note that this may or may not match the actual device burst size.
+    /// For some endpoints, the burst size can vary dynamically.
+    /// But these tend to be devices with high latency.
+    ///
+    /// Available since API level 26.
+    fn get_frames_per_burst(&self) -> i32 {
+        unsafe { ffi::AAudioStream_getFramesPerBurst(self.raw()) }
+    }
+
+    /// Query maximum buffer capacity in frames.
+    ///
+    /// Available since API level 26.
+    fn get_buffer_capacity_in_frames(&self) -> i32 {
+        unsafe { ffi::AAudioStream_getBufferCapacityInFrames(self.raw()) }
+    }
+
+    /// Query the size of the buffer that will be passed to the dataProc callback
+    /// in the numFrames parameter.
+    ///
+    /// This call can be used if the application needs to know the value of numFrames before
+    /// the stream is started. This is not normally necessary.
+    ///
+    /// If a specific size was requested by calling
+    /// `AAudioStreamBuilder::set_frames_per_data_callback()` then this will be the same size.
+    ///
+    /// If `AAudioStreamBuilder::set_frames_per_data_callback()` was not called then this will
+    /// return the size chosen by AAudio, or 0.
+    ///
+    /// 0 indicates that the callback buffer size for this stream
+    /// may vary from one dataProc callback to the next.
+    ///
+    /// Available since API level 26.
+    fn get_frames_per_data_callback(&self) -> i32 {
+        unsafe { ffi::AAudioStream_getFramesPerDataCallback(self.raw()) }
+    }
+
+    /// An XRun is an Underrun or an Overrun.
+    /// During playing, an underrun will occur if the stream is not written in time
+    /// and the system runs out of valid data.
+    /// During recording, an overrun will occur if the stream is not read in time
+    /// and there is no place to put the incoming data so it is discarded.
+    ///
+    /// An underrun or overrun can cause an audible "pop" or "glitch".
+    ///
+    /// Note that some INPUT devices may not support this function.
+    /// In that case a 0 will always be returned.
+    ///
+    /// Available since API level 26.
+    fn get_x_run_count(&self) -> i32 {
+        unsafe { ffi::AAudioStream_getXRunCount(self.raw()) }
+    }
+
+    /// Returns the actual device ID.
+    ///
+    /// Available since API level 26.
+    fn get_device_id(&self) -> i32 {
+        unsafe { ffi::AAudioStream_getDeviceId(self.raw()) }
+    }
+
+    /// Returns the actual data format.
+    ///
+    /// Available since API level 26.
+    fn get_format(&self) -> Format {
+        let val = unsafe { ffi::AAudioStream_getFormat(self.raw()) };
+        Format::from_i32(val)
+    }
+
+    /// Provide actual sharing mode.
+    ///
+    /// Available since API level 26.
+    fn get_sharing_mode(&self) -> SharingMode {
+        let val = unsafe { ffi::AAudioStream_getSharingMode(self.raw()) };
+        SharingMode::from_i32(val)
+    }
+
+    /// Get the performance mode used by the stream.
+    ///
+    /// Available since API level 26.
+    fn get_performance_mode(&self) -> PerformanceMode {
+        let val = unsafe { ffi::AAudioStream_getPerformanceMode(self.raw()) };
+        PerformanceMode::from_i32(val)
+    }
+
+    /// Available since API level 26.
+    fn get_direction(&self) -> Direction {
+        let val = unsafe { ffi::AAudioStream_getDirection(self.raw()) };
+        Direction::from_i32(val)
+    }
+
+    /// Returns the number of frames that have been written since the stream was created.
+    /// For an output stream, this will be advanced by the application calling `write()`
+    /// or by a data callback.
+    /// For an input stream, this will be advanced by the endpoint.
+    ///
+    /// The frame position is monotonically increasing.
+    ///
+    /// Available since API level 26.
+    fn get_frames_written(&self) -> i64 {
+        unsafe { ffi::AAudioStream_getFramesWritten(self.raw()) }
+    }
+
+    /// Returns the number of frames that have been read since the stream was created.
+    /// For an output stream, this will be advanced by the endpoint.
+    /// For an input stream, this will be advanced by the application calling `read()`
+    /// or by a data callback.
+    ///
+    /// The frame position is monotonically increasing.
+    ///
+    /// Available since API level 26.
+    fn get_frames_read(&self) -> i64 {
+        unsafe { ffi::AAudioStream_getFramesRead(self.raw()) }
+    }
+
+    /// Passes back the session ID associated with this stream.
+    ///
+    /// The session ID can be used to associate a stream with effects processors.
+    /// The effects are controlled using the Android AudioEffect Java API.
+    ///
+    /// If `AAudioStreamBuilder::set_session_id()` was called with 0
+    /// then a new session ID should be allocated once when the stream is opened.
+    ///
+    /// If `AAudioStreamBuilder::set_session_id()` was called with a previously allocated
+    /// session ID then that value should be returned.
+    ///
+    /// If `AAudioStreamBuilder::set_session_id()` was not called then this function should
+    /// return -1.
+    ///
+    /// The sessionID for a stream should not change once the stream has been opened.
+    ///
+    /// Available since API level 28.
+    fn get_session_id(&self) -> i32 {
+        unsafe { ffi::AAudioStream_getSessionId(self.raw()) }
+    }
+
+    /// Return the use case for the stream.
+    ///
+    /// Available since API level 28.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    fn get_usage(&self) -> Result<Usage, Error> {
+        require("AAudioStream_getUsage", "get_usage")?;
+        let val = unsafe { ffi::AAudioStream_getUsage(self.raw()) };
+        Ok(Usage::from_i32(val))
+    }
+
+    /// Return the content type for the stream.
+    ///
+    /// Available since API level 28.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    fn get_content_type(&self) -> Result<ContentType, Error> {
+        require("AAudioStream_getContentType", "get_content_type")?;
+        let val = unsafe { ffi::AAudioStream_getContentType(self.raw()) };
+        Ok(ContentType::from_i32(val))
+    }
+
+    /// Return the input preset for the stream.
+    ///
+    /// Available since API level 28.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    fn get_input_preset(&self) -> Result<InputPreset, Error> {
+        require("AAudioStream_getInputPreset", "get_input_preset")?;
+        let val = unsafe { ffi::AAudioStream_getInputPreset(self.raw()) };
+        Ok(InputPreset::from_i32(val))
+    }
+
+    /// Return the policy that determines whether the audio may or may not be captured
+    /// by other apps or the system.
+    ///
+    /// Available since API level 29.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    fn get_allowed_capture_policy(&self) -> Result<AllowedCapturePolicy, Error> {
+        require(
+            "AAudioStream_getAllowedCapturePolicy",
+            "get_allowed_capture_policy",
+        )?;
+        let val = unsafe { ffi::AAudioStream_getAllowedCapturePolicy(self.raw()) };
+        Ok(AllowedCapturePolicy::from_i32(val))
+    }
+
+    /// Return whether this input stream is marked as privacy sensitive or not.
+    ///
+    /// See `AAudioStreamBuilder::set_privacy_sensitive()`.
+    ///
+    /// Available since API level 30.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    fn is_privacy_sensitive(&self) -> Result<bool, Error> {
+        require("AAudioStream_isPrivacySensitive", "is_privacy_sensitive")?;
+        Ok(unsafe { ffi::AAudioStream_isPrivacySensitive(self.raw()) })
+    }
+
+    /// Return the spatialization behavior for the stream.
+    ///
+    /// If none was explicitly set, it will return the default `SpatializationBehavior::Auto`
+    /// behavior.
+    ///
+    /// Available since API level 32.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    fn get_spatialization_behavior(&self) -> Result<SpatializationBehavior, Error> {
+        require(
+            "AAudioStream_getSpatializationBehavior",
+            "get_spatialization_behavior",
+        )?;
+        let val = unsafe { ffi::AAudioStream_getSpatializationBehavior(self.raw()) };
+        Ok(SpatializationBehavior::from_i32(val))
+    }
+
+    /// Return whether the content of the stream is spatialized.
+    ///
+    /// Available since API level 32.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    fn is_content_spatialized(&self) -> Result<bool, Error> {
+        require(
+            "AAudioStream_isContentSpatialized",
+            "is_content_spatialized",
+        )?;
+        Ok(unsafe { ffi::AAudioStream_isContentSpatialized(self.raw()) })
+    }
+
+    /// Returns the time at which a particular frame was presented.
+    /// This can be used to synchronize audio with video or MIDI.
+    /// It can also be used to align a recorded stream with a playback stream.
+    ///
+    /// Timestamps are only valid when the stream is in `Started` state.
+    /// `InvalidState` will be returned if the stream is not started.
+    /// Note that because request_start() is asynchronous, timestamps will not be valid until
+    /// a short time after calling request_start().
+    /// So `InvalidState` should not be considered a fatal error.
+    /// Just try calling again later.
+    ///
+    /// The position and time passed back are monotonically increasing.
+    ///
+    /// Available since API level 26.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - the clock of the returned time
+    fn get_timestamp(&self, clock: Clock) -> Result<Timestamp, Error> {
+        get_timestamp(self.raw(), clock, "get_timestamp")
+    }
+
+    /// Returns the time at which a particular frame was presented on the `Clock::Monotonic`
+    /// clock. See `get_timestamp()`.
+    ///
+    /// Available since API level 26.
+    fn get_timestamp_monotonic(&self) -> Result<Timestamp, Error> {
+        get_timestamp(self.raw(), Clock::Monotonic, "get_timestamp_monotonic")
+    }
+
+    /// Returns the channel count, sample rate and format of the hardware.
+    ///
+    /// These may differ from the values of the stream, in which case the framework
+    /// converts the data between them.
+    ///
+    /// Available since API level 34.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    fn get_hardware_info(&self) -> Result<HardwareInfo, Error> {
+        get_hardware_info(self.raw())
+    }
+
+    /// Return the delay of an offloaded stream in frames,
+    /// as set with `AAudioStream::set_offload_delay_padding()`.
+    ///
+    /// Available since API level 36.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    fn get_offload_delay(&self) -> Result<i32, Error> {
+        require("AAudioStream_getOffloadDelay", "get_offload_delay")?;
+        let result = unsafe { ffi::AAudioStream_getOffloadDelay(self.raw()) };
+        wrap_stream_result(self.raw(), result, "get_offload_delay")?;
+        Ok(result)
+    }
+
+    /// Return the padding of an offloaded stream in frames,
+    /// as set with `AAudioStream::set_offload_delay_padding()`.
+    ///
+    /// Available since API level 36.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    fn get_offload_padding(&self) -> Result<i32, Error> {
+        require("AAudioStream_getOffloadPadding", "get_offload_padding")?;
+        let result = unsafe { ffi::AAudioStream_getOffloadPadding(self.raw()) };
+        wrap_stream_result(self.raw(), result, "get_offload_padding")?;
+        Ok(result)
+    }
+
+    /// Query the current state of the client, eg. `Pausing`.
+    ///
+    /// This function will immediately return the state without updating the state.
+    /// If you want to update the client state based on the server state then
+    /// call `AAudioStream::wait_for_state_change()` with currentState
+    /// set to `Unknown` and a zero timeout.
+    ///
+    /// Available since API level 26.
+    fn get_state(&self) -> StreamState {
+        let val = unsafe { ffi::AAudioStream_getState(self.raw()) };
+        StreamState::from_i32(val)
+    }
+
+    /// Returns the configuration the stream was opened with.
+    ///
+    /// Available since API level 26.
+    fn get_config(&self) -> StreamConfig {
+        StreamConfig {
+            usage: self.get_usage().ok(),
+            content_type: self.get_content_type().ok(),
+            input_preset: self.get_input_preset().ok(),
+            allowed_capture_policy: self.get_allowed_capture_policy().ok(),
+            privacy_sensitive: self.is_privacy_sensitive().ok(),
+            spatialization_behavior: self.get_spatialization_behavior().ok(),
+            content_spatialized: self.is_content_spatialized().ok(),
+            ..get_basic_config(self)
+        }
+    }
+}
+
+impl StreamProperties for AAudioStream {}
+
+impl<'a> StreamProperties for StreamView<'a> {}
+
+fn get_timestamp(
+    raw: *mut AAudioStreamRaw,
+    clock: Clock,
+    operation: &'static str,
+) -> Result<Timestamp, Error> {
+    let mut frame_position = MaybeUninit::uninit();
+    let mut time_nanos = MaybeUninit::uninit();
+    let result = unsafe {
+        ffi::AAudioStream_getTimestamp(
+            raw,
+            clock.clock_id(),
+            frame_position.as_mut_ptr(),
+            time_nanos.as_mut_ptr(),
+        )
+    };
+    wrap_stream_result(raw, result, operation)?;
+    Ok(unsafe {
+        Timestamp {
+            frame_position: frame_position.assume_init(),
+            time_nanos: time_nanos.assume_init(),
+        }
+    })
+}
+
+fn get_hardware_info(raw: *mut AAudioStreamRaw) -> Result<HardwareInfo, Error> {
+    require("AAudioStream_getHardwareChannelCount", "get_hardware_info")?;
+    require("AAudioStream_getHardwareSampleRate", "get_hardware_info")?;
+    require("AAudioStream_getHardwareFormat", "get_hardware_info")?;
+    unsafe {
+        Ok(HardwareInfo {
+            channel_count: ffi::AAudioStream_getHardwareChannelCount(raw),
+            sample_rate: ffi::AAudioStream_getHardwareSampleRate(raw),
+            format: Format::from_i32(ffi::AAudioStream_getHardwareFormat(raw)),
+        })
+    }
+}
+
+/// Returns the configuration of the stream, leaving out the values added after API level 26,
+/// so that it can be read on any device.
+pub(crate) fn get_basic_config<S: StreamProperties + ?Sized>(stream: &S) -> StreamConfig {
+    StreamConfig {
+        device_id: stream.get_device_id(),
+        direction: stream.get_direction(),
+        sharing_mode: stream.get_sharing_mode(),
+        performance_mode: stream.get_performance_mode(),
+        format: stream.get_format(),
+        sample_rate: stream.get_sample_rate(),
+        channel_count: stream.get_channel_count(),
+        frames_per_burst: stream.get_frames_per_burst(),
+        buffer_capacity_in_frames: stream.get_buffer_capacity_in_frames(),
+        usage: None,
+        content_type: None,
+        input_preset: None,
+        allowed_capture_policy: None,
+        privacy_sensitive: None,
+        spatialization_behavior: None,
+        content_spatialized: None,
+    }
+}
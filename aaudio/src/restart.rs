@@ -0,0 +1,105 @@
+use std::ffi::c_void;
+use std::sync::{Mutex, OnceLock, PoisonError, Weak};
+
+use crate::handle::StreamInner;
+use crate::panic::PanicHandler;
+use crate::{ffi, AAudioStreamRaw, Error, RouteChangeCallback, StreamView};
+
+/// The state of a stream opened with `AAudioStreamBuilder::set_auto_restart()`.
+pub(crate) struct Restart {
+    /// The error callback set by the application, called for errors other than
+    /// a disconnect and when the stream cannot be reopened.
+    error_callback: ffi::ErrorCallback,
+    error_user_data: *mut c_void,
+    route_change_callback: Mutex<Box<RouteChangeCallback>>,
+    panic: *const PanicHandler,
+    /// The stream being restarted, set once it is opened.
+    stream: OnceLock<Weak<StreamInner>>,
+}
+
+impl Restart {
+    pub(crate) fn new(
+        error_callback: ffi::ErrorCallback,
+        error_user_data: *mut c_void,
+        route_change_callback: Box<RouteChangeCallback>,
+        panic: *const PanicHandler,
+    ) -> Self {
+        Self {
+            error_callback,
+            error_user_data,
+            route_change_callback: Mutex::new(route_change_callback),
+            panic,
+            stream: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn attach(&self, stream: Weak<StreamInner>) {
+        let _ = self.stream.set(stream);
+    }
+
+    /// Passes the error to the error callback of the application.
+    unsafe fn forward_error(&self, stream: *mut AAudioStreamRaw, error: i32) {
+        if let Some(callback) = self.error_callback {
+            callback(stream, self.error_user_data, error);
+        }
+    }
+}
+
+pub(crate) unsafe extern "C" fn raw_restart_error_callback(
+    stream: *mut AAudioStreamRaw,
+    user_data: *mut c_void,
+    error: i32,
+) {
+    let restart = &*(user_data as *const Restart);
+    (*restart.panic).catch_other("error_callback", || {
+        if error != ffi::ERROR_DISCONNECTED {
+            restart.forward_error(stream, error);
+            return;
+        }
+        // The stream is being closed if it cannot be upgraded.
+        let inner = match restart.stream.get().and_then(Weak::upgrade) {
+            Some(inner) => inner,
+            None => return,
+        };
+        // Streams must not be closed or reopened from the error callback.
+        let stream = SendPtr(stream);
+        let worker = inner.worker().clone();
+        worker.run(move || {
+            let stream = stream;
+            let restart = match inner.restart() {
+                Some(restart) => restart,
+                None => return,
+            };
+            // Errors of the streams replaced by a previous restart are ignored.
+            match inner.reopen(stream.0) {
+                Ok(Some(raw)) => {
+                    let mut route_change_callback = restart
+                        .route_change_callback
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner);
+                    inner
+                        .panic_handler()
+                        .catch_other("route_change_callback", || {
+                            route_change_callback(&StreamView::new(raw))
+                        });
+                }
+                Ok(None) => {}
+                Err(error) => report_restart_error(&inner, error),
+            }
+        });
+    });
+}
+
+/// Passes the error of a failed restart to the error callback of the application.
+fn report_restart_error(inner: &StreamInner, error: Error) {
+    if let Some(handler) = inner.error_handler() {
+        // The stream is kept open by `inner` during the call.
+        unsafe { handler.call(inner.raw(), error) };
+    } else if let Some(object) = inner.callback_object() {
+        object.handle_error(error);
+    }
+}
+
+struct SendPtr(*mut AAudioStreamRaw);
+
+unsafe impl Send for SendPtr {}
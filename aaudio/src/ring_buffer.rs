@@ -0,0 +1,247 @@
+use std::cell::UnsafeCell;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{Error, ErrorKind, ResultCode, Sample};
+
+/// How long a blocking push sleeps while the ring buffer is full.
+const PUSH_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Creates a wait-free single-producer single-consumer ring buffer of samples, eg. to pass
+/// audio decoded on a normal thread to the data callback of a stream.
+///
+/// The capacity is rounded up to a power of two. Fails with `ResultCode::IllegalArgument`
+/// if the rounded capacity overflows or the buffer would exceed `isize::MAX` bytes. See
+/// `AAudioStreamBuilder::open_stream_with_ring_buffer()` for a stream playing the samples.
+pub fn ring_buffer<T: Sample>(
+    capacity: usize,
+) -> Result<(RingBufferProducer<T>, RingBufferConsumer<T>), Error> {
+    let capacity = capacity
+        .max(1)
+        .checked_next_power_of_two()
+        .filter(|&capacity| {
+            capacity
+                .checked_mul(mem::size_of::<T>())
+                .is_some_and(|len| len <= isize::MAX as usize)
+        })
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::AAudio(ResultCode::IllegalArgument),
+                "ring_buffer",
+            )
+        })?;
+    let shared = Arc::new(Shared {
+        buffer: (0..capacity)
+            .map(|_| UnsafeCell::new(T::default()))
+            .collect(),
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+        underruns: AtomicU64::new(0),
+        overruns: AtomicU64::new(0),
+    });
+    Ok((
+        RingBufferProducer {
+            shared: shared.clone(),
+        },
+        RingBufferConsumer { shared },
+    ))
+}
+
+struct Shared<T> {
+    buffer: Box<[UnsafeCell<T>]>,
+    /// The total number of samples popped, wrapping around. Only stored by the consumer.
+    read: AtomicUsize,
+    /// The total number of samples pushed, wrapping around. Only stored by the producer.
+    write: AtomicUsize,
+    underruns: AtomicU64,
+    overruns: AtomicU64,
+}
+
+// The producer only writes the samples between `write` and `read + capacity`,
+// and the consumer only reads the samples between `read` and `write`.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T: Sample> Shared<T> {
+    fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn len(&self) -> usize {
+        let write = self.write.load(Ordering::Acquire);
+        let read = self.read.load(Ordering::Acquire);
+        write.wrapping_sub(read)
+    }
+
+    /// Returns the pointer to the sample at the wrapping `position`, and the number
+    /// of samples until the end of the buffer.
+    fn slot(&self, position: usize) -> (*mut T, usize) {
+        // The capacity is a power of two, so wrapping positions stay consistent.
+        let index = position & (self.capacity() - 1);
+        (
+            UnsafeCell::raw_get(self.buffer[index..].as_ptr()),
+            self.capacity() - index,
+        )
+    }
+
+    /// Copies `samples` to the buffer from the wrapping `position`.
+    unsafe fn copy_in(&self, position: usize, samples: &[T]) {
+        let (slot, until_end) = self.slot(position);
+        let first = samples.len().min(until_end);
+        ptr::copy_nonoverlapping(samples.as_ptr(), slot, first);
+        let (start, _) = self.slot(0);
+        ptr::copy_nonoverlapping(samples[first..].as_ptr(), start, samples.len() - first);
+    }
+
+    /// Copies samples from the buffer from the wrapping `position` to `out`.
+    unsafe fn copy_out(&self, position: usize, out: &mut [T]) {
+        let (slot, until_end) = self.slot(position);
+        let first = out.len().min(until_end);
+        ptr::copy_nonoverlapping(slot, out.as_mut_ptr(), first);
+        let (start, _) = self.slot(0);
+        ptr::copy_nonoverlapping(start, out[first..].as_mut_ptr(), out.len() - first);
+    }
+}
+
+/// The half of a ring buffer that pushes samples, returned from `ring_buffer()`.
+pub struct RingBufferProducer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Sample> RingBufferProducer<T> {
+    /// Pushes as many samples of `samples` as there is room for, without blocking.
+    /// Returns the number of samples pushed.
+    ///
+    /// Counts an overrun if not all of the samples could be pushed.
+    pub fn push(&mut self, samples: &[T]) -> usize {
+        let pushed = self.push_available(samples);
+        if pushed < samples.len() {
+            self.shared.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+        pushed
+    }
+
+    /// Pushes the samples of `samples`, waiting for room in the buffer.
+    /// Returns the number of samples pushed.
+    ///
+    /// Fewer samples are pushed if the call runs out of time or the consumer is dropped,
+    /// eg. because the stream was closed. Counts an overrun if the call runs out of time.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - The samples to push.
+    /// * `timeout_nanoseconds` - Maximum number of nanoseconds to wait for completion.
+    pub fn push_blocking(&mut self, samples: &[T], timeout_nanoseconds: i64) -> usize {
+        let deadline = Instant::now() + Duration::from_nanos(timeout_nanoseconds.max(0) as u64);
+        let mut pushed = self.push_available(samples);
+        // The consumer never blocks, so the producer polls for room in the buffer.
+        while pushed < samples.len() && Arc::strong_count(&self.shared) > 1 {
+            let now = Instant::now();
+            if now >= deadline {
+                self.shared.overruns.fetch_add(1, Ordering::Relaxed);
+                break;
+            }
+            thread::sleep(PUSH_POLL_INTERVAL.min(deadline - now));
+            pushed += self.push_available(&samples[pushed..]);
+        }
+        pushed
+    }
+
+    fn push_available(&mut self, samples: &[T]) -> usize {
+        let shared = &*self.shared;
+        let write = shared.write.load(Ordering::Relaxed);
+        let free = shared.capacity() - write.wrapping_sub(shared.read.load(Ordering::Acquire));
+        let count = samples.len().min(free);
+        unsafe { shared.copy_in(write, &samples[..count]) };
+        shared
+            .write
+            .store(write.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    /// Returns the number of samples that can be pushed without blocking.
+    pub fn free_len(&self) -> usize {
+        self.shared.capacity() - self.shared.len()
+    }
+
+    /// Returns the number of samples the buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity()
+    }
+
+    /// Returns the number of times the consumer ran out of samples.
+    ///
+    /// This complements `StreamProperties::get_x_run_count()`, which counts the underruns
+    /// of the stream itself.
+    pub fn get_underrun_count(&self) -> u64 {
+        self.shared.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of times samples could not be pushed because the buffer was full.
+    pub fn get_overrun_count(&self) -> u64 {
+        self.shared.overruns.load(Ordering::Relaxed)
+    }
+}
+
+/// The half of a ring buffer that pops samples, returned from `ring_buffer()`.
+///
+/// Popping never blocks nor allocates, so it can be used in a data callback.
+pub struct RingBufferConsumer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Sample> RingBufferConsumer<T> {
+    /// Pops as many samples as available into `out`, without blocking.
+    /// Returns the number of samples popped.
+    pub fn pop(&mut self, out: &mut [T]) -> usize {
+        let shared = &*self.shared;
+        let read = shared.read.load(Ordering::Relaxed);
+        let available = shared.write.load(Ordering::Acquire).wrapping_sub(read);
+        let count = out.len().min(available);
+        unsafe { shared.copy_out(read, &mut out[..count]) };
+        shared
+            .read
+            .store(read.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    /// Fills `out` with whole frames of `channel_count` samples, and the rest with silence.
+    ///
+    /// Counts an underrun if `out` could not be filled.
+    pub(crate) fn pop_frames_or_silence(&mut self, out: &mut [T], channel_count: usize) {
+        let available = self.len().min(out.len());
+        let count = self.pop(&mut out[..available - available % channel_count.max(1)]);
+        if count < out.len() {
+            out[count..].fill(T::default());
+            self.shared.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the number of samples that can be popped.
+    pub fn len(&self) -> usize {
+        self.shared.len()
+    }
+
+    /// Returns `true` if there are no samples to pop.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of samples the buffer can hold.
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity()
+    }
+
+    /// See `RingBufferProducer::get_underrun_count()`.
+    pub fn get_underrun_count(&self) -> u64 {
+        self.shared.underruns.load(Ordering::Relaxed)
+    }
+
+    /// See `RingBufferProducer::get_overrun_count()`.
+    pub fn get_overrun_count(&self) -> u64 {
+        self.shared.overruns.load(Ordering::Relaxed)
+    }
+}
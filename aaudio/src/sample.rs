@@ -0,0 +1,85 @@
+use crate::Format;
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for i16 {}
+    impl Sealed for f32 {}
+    impl Sealed for i32 {}
+    impl Sealed for super::I24 {}
+}
+
+/// A type of a single audio sample, matching one of the stream data formats.
+///
+/// Buffers of samples are interleaved: a frame contains one sample for each channel.
+///
+/// This trait is sealed and implemented for `i16` (`Format::I16`), `f32` (`Format::F32`),
+/// `I24` (`Format::I24Packed`) and `i32` (`Format::I32`).
+pub trait Sample: private::Sealed + Copy + Default + Send + 'static {
+    /// The stream format that stores samples of this type.
+    const FORMAT: Format;
+}
+
+impl Sample for i16 {
+    const FORMAT: Format = Format::I16;
+}
+
+impl Sample for f32 {
+    const FORMAT: Format = Format::F32;
+}
+
+impl Sample for i32 {
+    const FORMAT: Format = Format::I32;
+}
+
+impl Sample for I24 {
+    const FORMAT: Format = Format::I24Packed;
+}
+
+/// A 24-bit sample packed into 3 little-endian bytes, as used by `Format::I24Packed`.
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct I24([u8; 3]);
+
+impl I24 {
+    /// The smallest value of a sample, -8388608.
+    pub const MIN: I24 = I24([0x00, 0x00, 0x80]);
+    /// The largest value of a sample, 8388607.
+    pub const MAX: I24 = I24([0xFF, 0xFF, 0x7F]);
+
+    /// Creates a sample from the lower 24 bits of `value`.
+    ///
+    /// Values out of the range of the sample wrap around. Use `I24::saturating_from_i32()`
+    /// to clamp them instead.
+    pub const fn from_i32(value: i32) -> Self {
+        let bytes = value.to_le_bytes();
+        I24([bytes[0], bytes[1], bytes[2]])
+    }
+
+    /// Creates a sample from `value`, clamped to the range of the sample.
+    pub fn saturating_from_i32(value: i32) -> Self {
+        Self::from_i32(value.clamp(Self::MIN.to_i32(), Self::MAX.to_i32()))
+    }
+
+    /// Returns the value of the sample.
+    pub const fn to_i32(self) -> i32 {
+        // Place the sample in the upper bytes and shift it back to extend the sign.
+        i32::from_le_bytes([0, self.0[0], self.0[1], self.0[2]]) >> 8
+    }
+
+    /// Creates a sample from its little-endian bytes.
+    pub const fn from_le_bytes(bytes: [u8; 3]) -> Self {
+        I24(bytes)
+    }
+
+    /// Returns the little-endian bytes of the sample.
+    pub const fn to_le_bytes(self) -> [u8; 3] {
+        self.0
+    }
+}
+
+impl From<I24> for i32 {
+    fn from(sample: I24) -> i32 {
+        sample.to_i32()
+    }
+}
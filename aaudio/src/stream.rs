@@ -0,0 +1,256 @@
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+use crate::properties::private::Sealed;
+use crate::{AAudioStream, AAudioStreamRaw, AudioFrame, Error, StreamProperties, StreamState};
+
+/// An output stream that plays frames of type `F`, opened with
+/// `AAudioStreamBuilder::open_output()`.
+///
+/// The getters of `StreamProperties` and the methods of `AAudioStream` that take `&self`
+/// are available. Frames can only be written as `F`, with `OutputStream::write()`.
+///
+/// Reading from an output stream does not compile:
+///
+/// ```compile_fail,E0061
+/// # use aaudio::{OutputStream, Stereo};
+/// fn record(stream: &mut OutputStream<Stereo<f32>>, buffer: &mut [Stereo<f32>]) {
+///     stream.read(buffer, 0);
+/// }
+/// ```
+///
+/// ```compile_fail,E0596
+/// # use aaudio::{OutputStream, Stereo};
+/// fn record(stream: &mut OutputStream<Stereo<f32>>, buffer: &mut [Stereo<f32>]) {
+///     stream.read_frames(buffer, 0);
+/// }
+/// ```
+pub struct OutputStream<F> {
+    stream: AAudioStream,
+    _frame: PhantomData<fn() -> F>,
+}
+
+impl<F: AudioFrame> OutputStream<F> {
+    pub(crate) fn new(stream: AAudioStream) -> Self {
+        Self {
+            stream,
+            _frame: PhantomData,
+        }
+    }
+
+    /// Write the frames of `buffer` to the stream.
+    /// Returns the number of frames actually written.
+    ///
+    /// See `AAudioStream::write_frames()`.
+    ///
+    /// Available since API level 26.
+    pub fn write(&mut self, buffer: &[F], timeout_nanoseconds: i64) -> Result<u32, Error> {
+        self.stream.write_frames(buffer, timeout_nanoseconds)
+    }
+
+    /// Returns the number of frames that were written to the stream but not played yet.
+    ///
+    /// Available since API level 26.
+    pub fn get_frames_queued(&self) -> i64 {
+        self.stream.get_frames_written() - self.stream.get_frames_read()
+    }
+
+    /// See `AAudioStream::request_start()`.
+    ///
+    /// Available since API level 26.
+    pub fn request_start(&mut self) -> Result<(), Error> {
+        self.stream.request_start()
+    }
+
+    /// See `AAudioStream::request_pause()`.
+    ///
+    /// Available since API level 26.
+    pub fn request_pause(&mut self) -> Result<(), Error> {
+        self.stream.request_pause()
+    }
+
+    /// See `AAudioStream::request_flush()`.
+    ///
+    /// Available since API level 26.
+    pub fn request_flush(&mut self) -> Result<(), Error> {
+        self.stream.request_flush()
+    }
+
+    /// See `AAudioStream::request_stop()`.
+    ///
+    /// Available since API level 26.
+    pub fn request_stop(&mut self) -> Result<(), Error> {
+        self.stream.request_stop()
+    }
+
+    /// See `AAudioStream::release()`.
+    ///
+    /// Available since API level 30.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    pub fn release(&mut self) -> Result<(), Error> {
+        self.stream.release()
+    }
+
+    /// See `AAudioStream::wait_for_state_change()`.
+    ///
+    /// Available since API level 26.
+    pub fn wait_for_state_change(
+        &mut self,
+        input_state: StreamState,
+        timeout_nanos: i64,
+    ) -> Result<StreamState, Error> {
+        self.stream
+            .wait_for_state_change(input_state, timeout_nanos)
+    }
+
+    /// See `AAudioStream::set_buffer_size_in_frames()`.
+    ///
+    /// Available since API level 26.
+    pub fn set_buffer_size_in_frames(&mut self, num_frames: i32) -> Result<(), Error> {
+        self.stream.set_buffer_size_in_frames(num_frames)
+    }
+
+    /// See `AAudioStream::set_offload_delay_padding()`.
+    ///
+    /// Available since API level 36.
+    pub fn set_offload_delay_padding(
+        &mut self,
+        delay_in_frames: i32,
+        padding_in_frames: i32,
+    ) -> Result<(), Error> {
+        self.stream
+            .set_offload_delay_padding(delay_in_frames, padding_in_frames)
+    }
+
+    /// See `AAudioStream::set_offload_end_of_stream()`.
+    ///
+    /// Available since API level 36.
+    pub fn set_offload_end_of_stream(&mut self) -> Result<(), Error> {
+        self.stream.set_offload_end_of_stream()
+    }
+}
+
+impl<F> Deref for OutputStream<F> {
+    type Target = AAudioStream;
+
+    fn deref(&self) -> &AAudioStream {
+        &self.stream
+    }
+}
+
+impl<F> Sealed for OutputStream<F> {
+    fn raw(&self) -> *mut AAudioStreamRaw {
+        self.stream.raw()
+    }
+}
+
+impl<F> StreamProperties for OutputStream<F> {}
+
+/// An input stream that records frames of type `F`, opened with
+/// `AAudioStreamBuilder::open_input()`.
+///
+/// The getters of `StreamProperties` and the methods of `AAudioStream` that take `&self`
+/// are available. Frames can only be read as `F`, with `InputStream::read()`.
+///
+/// Writing to an input stream does not compile:
+///
+/// ```compile_fail,E0061
+/// # use aaudio::{InputStream, Stereo};
+/// fn play(stream: &mut InputStream<Stereo<f32>>, buffer: &[Stereo<f32>]) {
+///     stream.write(buffer, 0);
+/// }
+/// ```
+///
+/// ```compile_fail,E0596
+/// # use aaudio::{InputStream, Stereo};
+/// fn play(stream: &mut InputStream<Stereo<f32>>, buffer: &[Stereo<f32>]) {
+///     stream.write_frames(buffer, 0);
+/// }
+/// ```
+pub struct InputStream<F> {
+    stream: AAudioStream,
+    _frame: PhantomData<fn() -> F>,
+}
+
+impl<F: AudioFrame> InputStream<F> {
+    pub(crate) fn new(stream: AAudioStream) -> Self {
+        Self {
+            stream,
+            _frame: PhantomData,
+        }
+    }
+
+    /// Read frames from the stream into `buffer`.
+    /// Returns the number of frames actually read.
+    ///
+    /// See `AAudioStream::read_frames()`.
+    ///
+    /// Available since API level 26.
+    pub fn read(&mut self, buffer: &mut [F], timeout_nanoseconds: i64) -> Result<u32, Error> {
+        self.stream.read_frames(buffer, timeout_nanoseconds)
+    }
+
+    /// Returns the number of frames that were recorded but not read from the stream yet.
+    ///
+    /// Available since API level 26.
+    pub fn get_frames_available(&self) -> i64 {
+        self.stream.get_frames_written() - self.stream.get_frames_read()
+    }
+
+    /// See `AAudioStream::request_start()`.
+    ///
+    /// Available since API level 26.
+    pub fn request_start(&mut self) -> Result<(), Error> {
+        self.stream.request_start()
+    }
+
+    /// See `AAudioStream::request_stop()`.
+    ///
+    /// Available since API level 26.
+    pub fn request_stop(&mut self) -> Result<(), Error> {
+        self.stream.request_stop()
+    }
+
+    /// See `AAudioStream::release()`.
+    ///
+    /// Available since API level 30.
+    /// Fails with `ErrorKind::FunctionUnavailable` if the function is not available on this device.
+    pub fn release(&mut self) -> Result<(), Error> {
+        self.stream.release()
+    }
+
+    /// See `AAudioStream::wait_for_state_change()`.
+    ///
+    /// Available since API level 26.
+    pub fn wait_for_state_change(
+        &mut self,
+        input_state: StreamState,
+        timeout_nanos: i64,
+    ) -> Result<StreamState, Error> {
+        self.stream
+            .wait_for_state_change(input_state, timeout_nanos)
+    }
+
+    /// See `AAudioStream::set_buffer_size_in_frames()`.
+    ///
+    /// Available since API level 26.
+    pub fn set_buffer_size_in_frames(&mut self, num_frames: i32) -> Result<(), Error> {
+        self.stream.set_buffer_size_in_frames(num_frames)
+    }
+}
+
+impl<F> Deref for InputStream<F> {
+    type Target = AAudioStream;
+
+    fn deref(&self) -> &AAudioStream {
+        &self.stream
+    }
+}
+
+impl<F> Sealed for InputStream<F> {
+    fn raw(&self) -> *mut AAudioStreamRaw {
+        self.stream.raw()
+    }
+}
+
+impl<F> StreamProperties for InputStream<F> {}
@@ -0,0 +1,90 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use aaudio::{
+    AAudioStreamBuilder, AllowedCapturePolicy, ContentType, Direction, InputPreset,
+    SpatializationBehavior, StreamProperties, Usage,
+};
+use common::lock;
+
+#[test]
+fn output_attributes() {
+    let _lock = lock();
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_usage(Usage::Alarm)
+        .unwrap()
+        .set_content_type(ContentType::Sonification)
+        .unwrap()
+        .set_allowed_capture_policy(AllowedCapturePolicy::AllowCaptureByAll)
+        .unwrap()
+        .open_stream()
+        .unwrap();
+    assert_eq!(stream.get_usage().unwrap(), Usage::Alarm);
+    assert_eq!(
+        stream.get_content_type().unwrap(),
+        ContentType::Sonification
+    );
+    assert_eq!(
+        stream.get_allowed_capture_policy().unwrap(),
+        AllowedCapturePolicy::AllowCaptureByAll
+    );
+
+    let config = stream.get_config();
+    assert_eq!(config.usage, Some(Usage::Alarm));
+    assert_eq!(config.content_type, Some(ContentType::Sonification));
+}
+
+#[test]
+fn input_attributes() {
+    let _lock = lock();
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_direction(Direction::Input)
+        .set_input_preset(InputPreset::VoiceRecognition)
+        .unwrap()
+        .set_privacy_sensitive(true)
+        .unwrap()
+        .open_stream()
+        .unwrap();
+    assert_eq!(
+        stream.get_input_preset().unwrap(),
+        InputPreset::VoiceRecognition
+    );
+    assert!(stream.is_privacy_sensitive().unwrap());
+    assert_eq!(stream.get_config().privacy_sensitive, Some(true));
+}
+
+#[test]
+fn spatialization() {
+    let _lock = lock();
+    let stream = AAudioStreamBuilder::new().unwrap().open_stream().unwrap();
+    assert_eq!(
+        stream.get_spatialization_behavior().unwrap(),
+        SpatializationBehavior::Auto
+    );
+    assert!(!stream.is_content_spatialized().unwrap());
+
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_spatialization_behavior(SpatializationBehavior::Never)
+        .unwrap()
+        .set_content_spatialized(true)
+        .unwrap()
+        .open_stream()
+        .unwrap();
+    assert_eq!(
+        stream.get_spatialization_behavior().unwrap(),
+        SpatializationBehavior::Never
+    );
+    assert!(stream.is_content_spatialized().unwrap());
+    let config = stream.get_config();
+    assert_eq!(
+        config.spatialization_behavior,
+        Some(SpatializationBehavior::Never)
+    );
+    assert_eq!(config.content_spatialized, Some(true));
+}
@@ -0,0 +1,74 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use aaudio::mock;
+use aaudio::{
+    AAudioStreamBuilder, CallbackResult, ErrorKind, ResultCode, StreamProperties, StreamState,
+};
+use common::lock;
+
+#[test]
+fn failed_setter_returns_the_builder() {
+    let _lock = lock();
+    let error = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_sample_rate(44100)
+        .set_package_name("invalid\0name")
+        .err()
+        .unwrap();
+    assert_eq!(
+        error.error().kind(),
+        ErrorKind::AAudio(ResultCode::IllegalArgument)
+    );
+    assert_eq!(error.error().operation(), "set_package_name");
+
+    // The settings made before the failure are kept.
+    let stream = error.into_builder().open_stream().unwrap();
+    assert_eq!(stream.get_sample_rate(), 44100);
+}
+
+#[test]
+fn builder_error_converts_to_error() {
+    fn open() -> Result<(), aaudio::Error> {
+        AAudioStreamBuilder::new()?.set_attribution_tag("\0")?;
+        Ok(())
+    }
+    let error = open().unwrap_err();
+    assert_eq!(error.operation(), "set_attribution_tag");
+}
+
+#[test]
+fn attribution() {
+    let _lock = lock();
+    let package_name = Some("com.example.recorder".to_string());
+    let attribution_tag = Some("voice-notes".to_string());
+    let (sender, receiver) = mpsc::channel();
+    let builder = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_package_name("com.example.recorder")
+        .unwrap()
+        .set_attribution_tag("voice-notes")
+        .unwrap()
+        .set_callbacks(|_, _, _| CallbackResult::Continue, |_, _, _| {})
+        .set_auto_restart(move |_| sender.send(mock::last_attribution()).unwrap());
+    let mut stream = builder.open_stream().unwrap();
+    assert_eq!(stream.get_state(), StreamState::Open);
+    assert_eq!(
+        mock::last_attribution(),
+        (package_name.clone(), attribution_tag.clone())
+    );
+
+    // The strings are read again when the stream is reopened.
+    drop(AAudioStreamBuilder::new().unwrap().open_stream().unwrap());
+    assert_eq!(mock::last_attribution(), (None, None));
+    stream.request_start().unwrap();
+    mock::disconnect_all();
+    let attribution = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(attribution, (package_name, attribution_tag));
+}
@@ -0,0 +1,240 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use aaudio::mock;
+use aaudio::{
+    AAudioStreamBuilder, AudioStreamCallback, CallbackResult, Error, ErrorKind, Format, ResultCode,
+    Stereo, StreamProperties, StreamState, StreamView,
+};
+use common::{lock, wait_until};
+
+#[test]
+fn typed_data_callback() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_frames_per_data_callback(32)
+        .set_data_callback(move |_, frames: &mut [Stereo<f32>], num_frames| {
+            let aligned = frames.as_ptr().align_offset(mem::align_of::<Stereo<f32>>()) == 0;
+            sender.send((frames.len(), num_frames, aligned)).unwrap();
+            CallbackResult::Stop
+        })
+        .open_stream()
+        .unwrap();
+    assert_eq!(stream.get_format(), Format::F32);
+    assert_eq!(stream.get_channel_count(), 2);
+
+    stream.request_start().unwrap();
+    wait_until(|| receiver.try_recv() == Ok((32, 32, true)));
+}
+
+#[test]
+fn interleaved_data_callback() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_channel_count(3)
+        .set_frames_per_data_callback(16)
+        .set_data_callback(move |_, samples: &mut [i16], num_frames| {
+            sender.send((samples.len(), num_frames)).unwrap();
+            CallbackResult::Stop
+        })
+        .open_stream()
+        .unwrap();
+    assert_eq!(stream.get_format(), Format::I16);
+
+    stream.request_start().unwrap();
+    wait_until(|| receiver.try_recv() == Ok((48, 16)));
+}
+
+#[test]
+fn callback_result_values() {
+    assert_eq!(i32::from(CallbackResult::Continue), 0);
+    assert_eq!(i32::from(CallbackResult::Stop), 1);
+}
+
+#[test]
+fn partial_data_callback() {
+    let _lock = lock();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls_clone = calls.clone();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_frames_per_data_callback(20)
+        .set_callbacks(|_, _, _| panic!("replaced"), |_, _, _| {})
+        .set_partial_data_callback(
+            move |_, _, num_frames| match calls_clone.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => num_frames / 2,
+                2 => 0,
+                _ => -1,
+            },
+            |_, _, _| {},
+        )
+        .unwrap()
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+
+    // A negative result stops the stream.
+    wait_until(|| stream.get_state() == StreamState::Stopped);
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+    assert_eq!(stream.get_frames_written(), 20);
+}
+
+#[test]
+fn partial_data_callback_out_of_range() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_partial_data_callback(|_, _, num_frames| num_frames + 1, |_, _, _| {})
+        .unwrap()
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+    wait_until(|| stream.get_state() == StreamState::Stopped);
+    assert_eq!(stream.get_frames_written(), 0);
+}
+
+/// Records the calls of an `AudioStreamCallback`.
+struct Recorder {
+    frames: i32,
+    events: mpsc::Sender<Event>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    AudioReady(i32),
+    BeforeClose(StreamState, ErrorKind, i32),
+    AfterClose(ErrorKind),
+}
+
+impl AudioStreamCallback for Recorder {
+    fn on_audio_ready(
+        &mut self,
+        _stream: &StreamView<'_>,
+        audio_data: &mut [u8],
+        num_frames: i32,
+    ) -> CallbackResult {
+        audio_data.fill(0);
+        self.frames += num_frames;
+        let _ = self.events.send(Event::AudioReady(self.frames));
+        CallbackResult::Continue
+    }
+
+    fn on_error_before_close(&mut self, stream: &StreamView<'_>, error: &Error) {
+        let event = Event::BeforeClose(stream.get_state(), error.kind(), self.frames);
+        self.events.send(event).unwrap();
+    }
+
+    fn on_error_after_close(&mut self, error: Error) {
+        self.events.send(Event::AfterClose(error.kind())).unwrap();
+    }
+}
+
+#[test]
+fn callback_object() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_frames_per_data_callback(32)
+        .set_callback(Recorder {
+            frames: 0,
+            events: sender,
+        })
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+    wait_until(|| matches!(receiver.try_recv(), Ok(Event::AudioReady(frames)) if frames >= 64));
+
+    mock::disconnect_all();
+    let disconnected = ErrorKind::AAudio(ResultCode::Disconnected);
+    let frames = loop {
+        match receiver.recv_timeout(Duration::from_secs(5)).unwrap() {
+            Event::AudioReady(_) => {}
+            Event::BeforeClose(state, kind, frames) => {
+                assert_eq!(state, StreamState::Disconnected);
+                assert_eq!(kind, disconnected);
+                break frames;
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    };
+    assert!(frames >= 64);
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+        Event::AfterClose(disconnected)
+    );
+    assert_eq!(stream.get_state(), StreamState::Closing);
+    drop(stream);
+    assert!(receiver.recv_timeout(Duration::from_secs(5)).is_err());
+}
+
+#[test]
+fn misaligned_buffer_stops_the_stream() {
+    let _lock = lock();
+    let device = mock::device();
+    mock::set_device(mock::MockDevice {
+        callback_buffer_offset: 2,
+        ..device
+    });
+    let (sender, receiver) = mpsc::channel();
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_data_callback(|_, _: &mut [f32], _| panic!("the callback must not be called"))
+        .set_error_callback(move |_, _, error| sender.send(error.kind()).unwrap())
+        .open_stream();
+    mock::set_device(device);
+    let mut stream = stream.unwrap();
+    stream.request_start().unwrap();
+
+    let kind = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(kind, ErrorKind::MisalignedBuffer { alignment: 4 });
+    wait_until(|| stream.get_state() == StreamState::Stopped);
+}
+
+#[test]
+fn callback_object_error_stops_the_stream() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_callback(Recorder {
+            frames: 0,
+            events: sender,
+        })
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+    wait_until(|| matches!(receiver.try_recv(), Ok(Event::AudioReady(_))));
+
+    mock::fail_all(i32::from(ResultCode::Timeout));
+    let timeout = ErrorKind::AAudio(ResultCode::Timeout);
+    loop {
+        match receiver.recv_timeout(Duration::from_secs(5)).unwrap() {
+            Event::AudioReady(_) => {}
+            Event::BeforeClose(state, kind, _) => {
+                assert_eq!(state, StreamState::Stopped);
+                assert_eq!(kind, timeout);
+                break;
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+        Event::AfterClose(timeout)
+    );
+    assert_eq!(stream.get_state(), StreamState::Closing);
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use aaudio::{AAudioStreamBuilder, ChannelMask, Stereo, StreamProperties};
+use common::lock;
+
+#[test]
+fn channel_mask_sets_the_channel_count() {
+    let _lock = lock();
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_channel_mask(ChannelMask::FIVE_POINT_ONE)
+        .unwrap()
+        .open_stream()
+        .unwrap();
+    assert_eq!(
+        stream.get_channel_mask().unwrap(),
+        ChannelMask::FIVE_POINT_ONE
+    );
+    assert_eq!(stream.get_channel_count(), 6);
+    assert_eq!(ChannelMask::FIVE_POINT_ONE.channel_count(), 6);
+}
+
+#[test]
+fn channel_mask_is_unspecified_by_default() {
+    let _lock = lock();
+    let stream = AAudioStreamBuilder::new().unwrap().open_stream().unwrap();
+    assert_eq!(stream.get_channel_mask().unwrap(), ChannelMask::UNSPECIFIED);
+}
+
+#[test]
+fn frame_type_keeps_a_matching_channel_mask() {
+    let _lock = lock();
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_channel_mask(ChannelMask::STEREO)
+        .unwrap()
+        .open_output::<Stereo<f32>>()
+        .unwrap();
+    assert_eq!(stream.get_channel_mask().unwrap(), ChannelMask::STEREO);
+}
@@ -0,0 +1,27 @@
+#![allow(dead_code)]
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Serializes the tests of a file that changes the virtual device or disconnects streams,
+/// since that affects every stream of the process.
+pub fn lock() -> MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Polls `condition` until it holds, panicking after a few seconds.
+pub fn wait_until<F: FnMut() -> bool>(mut condition: F) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !condition() {
+        assert!(
+            Instant::now() < deadline,
+            "timed out waiting for a condition"
+        );
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// One second, the timeout of blocking calls in the tests.
+pub const TIMEOUT: i64 = 1_000_000_000;
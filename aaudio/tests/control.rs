@@ -0,0 +1,97 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use aaudio::mock::{self, MockDevice};
+use aaudio::{
+    AAudioStreamBuilder, CallbackResult, PanicPolicy, ResultCode, StreamProperties, StreamState,
+};
+use common::{lock, wait_until};
+
+#[test]
+fn release_from_the_error_callback() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_callbacks(
+            |_, _, _| CallbackResult::Continue,
+            move |_, control, _| {
+                // Waiting for the result is allowed, the request runs on the worker thread.
+                let result = control.request_release().recv().unwrap();
+                sender.send(result.map_err(|error| error.kind())).unwrap();
+            },
+        )
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+    mock::disconnect_all();
+
+    let result = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(result, Ok(()));
+    assert_eq!(stream.get_state(), StreamState::Closing);
+}
+
+#[test]
+fn release_when_a_panic_is_reported() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_panic_policy(PanicPolicy::Silence)
+        .set_callbacks(
+            |_, _, _| panic!("data callback"),
+            move |_, control, _| {
+                // The panic is reported on the worker thread, which runs the request right away.
+                let result = control.request_release().recv().unwrap();
+                sender.send(result.map_err(|error| error.kind())).unwrap();
+            },
+        )
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+
+    let result = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(result, Ok(()));
+    assert_eq!(stream.get_state(), StreamState::Closing);
+}
+
+#[test]
+fn restart_with_a_stored_control() {
+    let _lock = lock();
+    let device = mock::device();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_callbacks(
+            |_, _, _| CallbackResult::Continue,
+            move |_, control, _| sender.send(control.clone()).unwrap(),
+        )
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+    mock::set_device(MockDevice {
+        device_id: 7,
+        ..device
+    });
+    mock::disconnect_all();
+
+    let control = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    let result = control.request_restart().recv().unwrap();
+    mock::set_device(device);
+    assert!(result.is_ok());
+    assert_eq!(stream.get_device_id(), 7);
+    wait_until(|| stream.get_state() == StreamState::Started);
+
+    control.request_stop().recv().unwrap().unwrap();
+    wait_until(|| stream.get_state() == StreamState::Stopped);
+
+    drop(stream);
+    let error = control.request_stop().recv().unwrap().unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::InvalidState));
+}
@@ -0,0 +1,117 @@
+#![cfg(all(feature = "dynamic", not(feature = "mock")))]
+
+extern crate aaudio;
+
+use std::env;
+use std::ffi::CString;
+use std::process::Command;
+use std::sync::Once;
+
+use aaudio::dynamic::{self, LoadError};
+use aaudio::{
+    AAudioStreamBuilder, ErrorKind, Format, ResultCode, StreamProperties, StreamState, Usage,
+};
+
+/// Builds the stub library of `tests/stub` and loads it instead of `libaaudio`.
+fn load_stub() {
+    static LOAD: Once = Once::new();
+    LOAD.call_once(|| {
+        let source = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/stub/aaudio_stub.rs");
+        let library = concat!(env!("CARGO_TARGET_TMPDIR"), "/libaaudio_stub.so");
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_owned());
+        let status = Command::new(rustc)
+            .args(["--crate-type", "cdylib", "-o", library, source])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        dynamic::load_library(&CString::new(library).unwrap()).unwrap();
+    });
+}
+
+#[test]
+fn library_is_loaded_once() {
+    load_stub();
+    assert_eq!(dynamic::load_error(), None);
+    let path = CString::new(dynamic::DEFAULT_LIBRARY).unwrap();
+    assert_eq!(dynamic::load_library(&path), Err(LoadError::AlreadyLoaded));
+}
+
+#[test]
+fn reports_available_functions() {
+    load_stub();
+    assert!(aaudio::is_available("AAudioStreamBuilder_setSampleRate"));
+    assert!(!aaudio::is_available("AAudioStreamBuilder_setUsage"));
+    assert!(!aaudio::is_available("AAudio_unknownFunction"));
+}
+
+#[test]
+fn missing_setter_returns_the_builder() {
+    load_stub();
+    let error = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_sample_rate(48000)
+        .set_usage(Usage::Game)
+        .err()
+        .unwrap();
+    assert_eq!(
+        error.error().kind(),
+        ErrorKind::FunctionUnavailable("AAudioStreamBuilder_setUsage")
+    );
+    assert_eq!(error.error().operation(), "set_usage");
+
+    let error = error
+        .into_builder()
+        .set_package_name("com.example.recorder")
+        .err()
+        .unwrap();
+    assert_eq!(
+        error.error().kind(),
+        ErrorKind::FunctionUnavailable("AAudioStreamBuilder_setPackageName")
+    );
+
+    let stream = error.into_builder().open_stream().unwrap();
+    assert_eq!(stream.get_sample_rate(), 48000);
+}
+
+#[test]
+fn config_leaves_out_missing_values() {
+    load_stub();
+    let mut stream = AAudioStreamBuilder::new().unwrap().open_stream().unwrap();
+    let config = stream.get_config();
+    assert_eq!(config.format, Format::I16);
+    assert_eq!(config.channel_count, 2);
+    assert_eq!(config.usage, None);
+    assert_eq!(config.spatialization_behavior, None);
+
+    let error = stream.request_start().unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::InvalidState));
+    assert_eq!(error.state(), Some(StreamState::Open));
+    assert_eq!(error.config(), Some(&config));
+
+    let error = stream.get_channel_mask().unwrap_err();
+    assert_eq!(
+        error.kind(),
+        ErrorKind::FunctionUnavailable("AAudioStream_getChannelMask")
+    );
+
+    let error = stream.get_usage().unwrap_err();
+    assert_eq!(
+        error.kind(),
+        ErrorKind::FunctionUnavailable("AAudioStream_getUsage")
+    );
+    assert!(stream.is_privacy_sensitive().is_err());
+
+    let error = stream.get_spatialization_behavior().unwrap_err();
+    assert_eq!(
+        error.kind(),
+        ErrorKind::FunctionUnavailable("AAudioStream_getSpatializationBehavior")
+    );
+    assert!(stream.is_content_spatialized().is_err());
+
+    let error = stream.get_hardware_info().unwrap_err();
+    assert_eq!(error.operation(), "get_hardware_info");
+
+    // Missing functions that return a result code fail with `Unimplemented`.
+    let error = stream.request_pause().unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::AAudio(ResultCode::Unimplemented));
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use std::sync::mpsc;
+
+use aaudio::{AAudioStreamBuilder, CallbackResult, Format, StreamProperties, I24};
+use common::{lock, wait_until, TIMEOUT};
+
+#[test]
+fn i24_conversions() {
+    assert_eq!(I24::MIN.to_i32(), -8388608);
+    assert_eq!(I24::MAX.to_i32(), 8388607);
+    assert_eq!(I24::from_i32(-1).to_le_bytes(), [0xFF, 0xFF, 0xFF]);
+    assert_eq!(I24::from_i32(0x123456).to_le_bytes(), [0x56, 0x34, 0x12]);
+    assert_eq!(i32::from(I24::from_le_bytes([0x00, 0x00, 0x80])), -8388608);
+    // Out of range values wrap around, unless saturated.
+    assert_eq!(I24::from_i32(8388608), I24::MIN);
+    assert_eq!(I24::saturating_from_i32(8388608), I24::MAX);
+    assert_eq!(I24::saturating_from_i32(i32::MIN), I24::MIN);
+}
+
+#[test]
+fn packed_24_bit_callback_buffer() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_format(Format::I24Packed)
+        .set_channel_count(2)
+        .set_frames_per_data_callback(10)
+        .set_callbacks(
+            move |_, data, num_frames| {
+                sender.send((data.len(), num_frames)).unwrap();
+                CallbackResult::Stop
+            },
+            |_, _, _| {},
+        )
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+    wait_until(|| receiver.try_recv() == Ok((60, 10)));
+}
+
+#[test]
+fn i32_samples() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_channel_count(1)
+        .open_output::<i32>()
+        .unwrap();
+    assert_eq!(stream.get_format(), Format::I32);
+    stream.request_start().unwrap();
+    assert_eq!(stream.write(&[i32::MAX; 480], TIMEOUT).unwrap(), 480);
+}
+
+#[test]
+fn i24_samples() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_channel_count(2)
+        .open_input::<I24>()
+        .unwrap();
+    assert_eq!(stream.get_format(), Format::I24Packed);
+    let mut buffer = [I24::MAX; 2 * 100];
+    stream.request_start().unwrap();
+    assert_eq!(stream.read(&mut buffer, TIMEOUT).unwrap(), 100);
+    assert!(buffer.iter().all(|&sample| sample == I24::default()));
+}
@@ -0,0 +1,53 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use std::mem;
+
+use aaudio::{AAudioStreamBuilder, Format, Frame, Mono, Stereo, StreamProperties, I24};
+use common::lock;
+
+#[test]
+fn frame_layout() {
+    assert_eq!(mem::size_of::<Mono<i16>>(), 2);
+    assert_eq!(mem::size_of::<Stereo<f32>>(), 8);
+    assert_eq!(mem::align_of::<Stereo<f32>>(), 4);
+    assert_eq!(mem::size_of::<Frame<I24, 6>>(), 18);
+    assert_eq!(mem::align_of::<Frame<I24, 6>>(), 1);
+
+    let frame: Frame<i16, 3> = [1, 2, 3].into();
+    assert_eq!(frame.0, [1, 2, 3]);
+    assert_eq!(Frame::<f32, 4>::default().0, [0.0; 4]);
+}
+
+#[test]
+fn frame_type_sets_format_and_channel_count() {
+    let _lock = lock();
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .open_output::<Frame<i16, 6>>()
+        .unwrap();
+    assert_eq!(stream.get_format(), Format::I16);
+    assert_eq!(stream.get_channel_count(), 6);
+
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .open_input::<Mono<i32>>()
+        .unwrap();
+    assert_eq!(stream.get_format(), Format::I32);
+    assert_eq!(stream.get_channel_count(), 1);
+}
+
+#[test]
+fn sample_type_keeps_the_channel_count() {
+    let _lock = lock();
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_channel_count(4)
+        .open_output::<f32>()
+        .unwrap();
+    assert_eq!(stream.get_format(), Format::F32);
+    assert_eq!(stream.get_channel_count(), 4);
+}
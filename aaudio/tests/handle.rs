@@ -0,0 +1,157 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use aaudio::{AAudioStreamBuilder, CallbackResult, StreamProperties, StreamState};
+use common::{lock, wait_until, TIMEOUT};
+
+/// Reports on a channel when it is dropped, ie. when the callbacks that own it are dropped.
+struct DropSignal(Sender<()>);
+
+impl Drop for DropSignal {
+    fn drop(&mut self) {
+        let _ = self.0.send(());
+    }
+}
+
+#[test]
+fn handle_controls_the_stream_from_other_threads() {
+    let _lock = lock();
+    let stream = AAudioStreamBuilder::new().unwrap().open_stream().unwrap();
+    let handle = stream.handle();
+    thread::spawn(move || {
+        handle.request_start().unwrap();
+        let state = handle
+            .wait_for_state_change(StreamState::Starting, TIMEOUT)
+            .unwrap();
+        assert_eq!(state, StreamState::Started);
+    })
+    .join()
+    .unwrap();
+    assert_eq!(stream.get_state(), StreamState::Started);
+
+    // The stream stays open while a handle exists.
+    let handle = stream.handle();
+    drop(stream);
+    assert_eq!(handle.get_state(), StreamState::Started);
+    handle.request_stop().unwrap();
+}
+
+#[test]
+fn handle_is_shared_between_threads() {
+    let _lock = lock();
+    let stream = AAudioStreamBuilder::new().unwrap().open_stream().unwrap();
+    let handle = Arc::new(stream.handle());
+    let capacity = handle.get_buffer_capacity_in_frames();
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let handle = handle.clone();
+            thread::spawn(move || {
+                for _ in 0..50 {
+                    handle.set_buffer_size_in_frames(capacity).unwrap();
+                    let _ = handle.get_state();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(handle.get_buffer_size_in_frames(), capacity);
+
+    handle.request_start().unwrap();
+    let waiter = {
+        let handle = handle.clone();
+        thread::spawn(move || handle.wait_for_state_change(StreamState::Starting, TIMEOUT))
+    };
+    assert_eq!(waiter.join().unwrap().unwrap(), StreamState::Started);
+    handle.request_pause().unwrap();
+    wait_until(|| handle.get_state() == StreamState::Paused);
+    handle.request_flush().unwrap();
+    wait_until(|| handle.get_state() == StreamState::Flushed);
+}
+
+#[test]
+fn handle_outlives_the_stream() {
+    let _lock = lock();
+    let stream = AAudioStreamBuilder::new().unwrap().open_stream().unwrap();
+    let handle = stream.handle();
+    let weak = handle.downgrade();
+    drop(stream);
+    let clone = handle.clone();
+    drop(handle);
+    assert!(weak.upgrade().is_some());
+    clone.request_start().unwrap();
+    wait_until(|| clone.get_state() == StreamState::Started);
+    drop(clone);
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn weak_handle_in_callbacks_does_not_keep_the_stream_open() {
+    let _lock = lock();
+    let (dropped_sender, dropped) = mpsc::channel();
+    let (state_sender, states) = mpsc::channel();
+    let weak = Arc::new(Mutex::new(None));
+    let weak_clone = weak.clone();
+    let signal = DropSignal(dropped_sender);
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_callbacks(
+            move |_, _, _| {
+                let _signal = &signal;
+                let weak = weak_clone.lock().unwrap();
+                if let Some(handle) = weak.as_ref().and_then(aaudio::WeakStreamHandle::upgrade) {
+                    let _ = state_sender.send(handle.get_state());
+                }
+                CallbackResult::Continue
+            },
+            |_, _, _| {},
+        )
+        .open_stream()
+        .unwrap();
+    *weak.lock().unwrap() = Some(stream.handle().downgrade());
+    stream.request_start().unwrap();
+    wait_until(|| states.try_recv() == Ok(StreamState::Started));
+
+    drop(stream);
+    dropped.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(weak.lock().unwrap().as_ref().unwrap().upgrade().is_none());
+}
+
+#[test]
+fn last_handle_dropped_by_a_callback_closes_the_stream() {
+    let _lock = lock();
+    let (dropped_sender, dropped) = mpsc::channel();
+    let slot = Arc::new(Mutex::new(None));
+    let slot_clone = slot.clone();
+    let signal = DropSignal(dropped_sender);
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_callbacks(
+            move |_, _, _| {
+                let _signal = &signal;
+                // Drops the only remaining reference to the stream.
+                drop(slot_clone.lock().unwrap().take());
+                CallbackResult::Continue
+            },
+            |_, _, _| {},
+        )
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+    *slot.lock().unwrap() = Some(stream.handle());
+    drop(stream);
+
+    match dropped.recv_timeout(Duration::from_secs(5)) {
+        Ok(()) | Err(RecvTimeoutError::Disconnected) => {}
+        Err(RecvTimeoutError::Timeout) => panic!("the stream was not closed"),
+    }
+}
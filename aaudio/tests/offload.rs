@@ -0,0 +1,71 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use aaudio::{AAudioStreamBuilder, Format, PerformanceMode, ResultCode, StreamProperties};
+use common::{lock, TIMEOUT};
+
+#[test]
+fn compressed_formats_require_offload() {
+    let _lock = lock();
+    let error = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_format(Format::Mp3)
+        .open_stream()
+        .err()
+        .unwrap();
+    assert_eq!(error.result_code(), Some(ResultCode::InvalidFormat));
+}
+
+#[test]
+fn offload_delay_padding() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new().unwrap().open_stream().unwrap();
+    let error = stream.set_offload_delay_padding(10, 20).unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::Unimplemented));
+
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_performance_mode(PerformanceMode::PowerSavingOffloaded)
+        .set_format(Format::Mp3)
+        .open_stream()
+        .unwrap();
+    assert!(stream.get_format().is_compressed());
+    stream.set_offload_delay_padding(10, 20).unwrap();
+    assert_eq!(stream.get_offload_delay().unwrap(), 10);
+    assert_eq!(stream.get_offload_padding().unwrap(), 20);
+    let error = stream.set_offload_delay_padding(-1, 0).unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::IllegalArgument));
+}
+
+#[test]
+fn presentation_end_callback() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_performance_mode(PerformanceMode::PowerSavingOffloaded)
+        .set_format(Format::Mp3)
+        .set_presentation_end_callback(move |stream| {
+            sender.send(stream.get_frames_read()).unwrap();
+        })
+        .unwrap()
+        .open_stream()
+        .unwrap();
+
+    // The end of stream can only be marked while the stream is running.
+    let error = stream.set_offload_end_of_stream().unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::InvalidState));
+
+    stream.request_start().unwrap();
+    let track = vec![0u8; 1024];
+    assert_eq!(stream.write(&track, 1024, TIMEOUT).unwrap(), 1024);
+    stream.set_offload_end_of_stream().unwrap();
+    let frames_read = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(frames_read >= 1024);
+}
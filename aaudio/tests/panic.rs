@@ -0,0 +1,206 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use std::any::Any;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use aaudio::mock;
+use aaudio::{
+    AAudioStreamBuilder, AudioStreamCallback, CallbackResult, Error, ErrorKind, PanicPolicy,
+    StreamProperties, StreamState, StreamView,
+};
+use common::{lock, wait_until};
+
+fn message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<&str>() {
+        Ok(message) => message.to_string(),
+        Err(payload) => *payload.downcast::<String>().unwrap(),
+    }
+}
+
+#[test]
+fn default_policy_aborts() {
+    assert_eq!(PanicPolicy::default(), PanicPolicy::Abort);
+}
+
+#[test]
+fn stop_policy_reports_to_the_error_callback() {
+    let _lock = lock();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls_clone = calls.clone();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_panic_policy(PanicPolicy::Stop)
+        .set_callbacks(
+            move |_, _, _| {
+                calls_clone.fetch_add(1, Ordering::SeqCst);
+                panic!("data callback");
+            },
+            move |_, _, error| sender.send(error).unwrap(),
+        )
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+
+    let error = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(error.kind(), ErrorKind::Panicked);
+    assert_eq!(error.operation(), "data_callback");
+    assert_eq!(error.panic_message(), Some("data callback"));
+    wait_until(|| stream.get_state() == StreamState::Stopped);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn silence_policy_keeps_the_stream_running() {
+    let _lock = lock();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls_clone = calls.clone();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_panic_policy(PanicPolicy::Silence)
+        .set_panic_hook(move |payload| sender.send(message(payload)).unwrap())
+        .set_callbacks(
+            move |_, _, _| {
+                calls_clone.fetch_add(1, Ordering::SeqCst);
+                panic!("sample {}", 7);
+            },
+            |_, _, _| panic!("the error callback must not be called"),
+        )
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+
+    let message = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(message, "sample 7");
+    let before = stream.get_frames_read();
+    wait_until(|| stream.get_frames_read() > before);
+    assert_eq!(stream.get_state(), StreamState::Started);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    stream.request_stop().unwrap();
+}
+
+#[test]
+fn error_callback_panic_is_reported_to_the_hook() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_panic_policy(PanicPolicy::Stop)
+        .set_panic_hook(move |payload| sender.send(message(payload)).unwrap())
+        .set_callbacks(
+            |_, _, _| CallbackResult::Continue,
+            |_, _, _| panic!("error callback"),
+        )
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+    mock::disconnect_all();
+
+    let message = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(message, "error callback");
+    assert_eq!(stream.get_state(), StreamState::Disconnected);
+}
+
+struct PanicBeforeClose(mpsc::Sender<String>);
+
+impl AudioStreamCallback for PanicBeforeClose {
+    fn on_audio_ready(
+        &mut self,
+        _stream: &StreamView<'_>,
+        _audio_data: &mut [u8],
+        _num_frames: i32,
+    ) -> CallbackResult {
+        CallbackResult::Continue
+    }
+
+    fn on_error_before_close(&mut self, _stream: &StreamView<'_>, _error: &Error) {
+        panic!("before close");
+    }
+
+    fn on_error_after_close(&mut self, _error: Error) {
+        self.0.send("after close".to_string()).unwrap();
+    }
+}
+
+#[test]
+fn error_hook_panic_is_reported_to_the_hook() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let hook_sender = sender.clone();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_panic_policy(PanicPolicy::Silence)
+        .set_panic_hook(move |payload| hook_sender.send(message(payload)).unwrap())
+        .set_callback(PanicBeforeClose(sender))
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+    mock::disconnect_all();
+
+    let mut messages = vec![
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+    ];
+    messages.sort();
+    assert_eq!(messages, ["after close", "before close"]);
+}
+
+#[test]
+fn route_change_panic_is_reported_to_the_error_callback() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_panic_policy(PanicPolicy::Silence)
+        .set_callbacks(
+            |_, _, _| CallbackResult::Continue,
+            move |_, _, error| sender.send(error).unwrap(),
+        )
+        .set_auto_restart(|_| panic!("route change"))
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+    mock::disconnect_all();
+
+    let error = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(error.kind(), ErrorKind::Panicked);
+    assert_eq!(error.operation(), "route_change_callback");
+    assert_eq!(error.panic_message(), Some("route change"));
+    assert!(stream.take_panic().is_none());
+}
+
+#[test]
+fn unreported_panic_is_kept_until_taken() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_panic_policy(PanicPolicy::Silence)
+        .set_callback(PanicBeforeClose(sender))
+        .open_stream()
+        .unwrap();
+    let handle = stream.handle();
+    handle.request_start().unwrap();
+    mock::disconnect_all();
+
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap(),
+        "after close"
+    );
+    // The panic is reported after the error is handled.
+    let mut payload = None;
+    wait_until(|| {
+        payload = handle.take_panic();
+        payload.is_some()
+    });
+    assert_eq!(message(payload.unwrap()), "before close");
+    assert!(stream.take_panic().is_none());
+}
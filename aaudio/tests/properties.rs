@@ -0,0 +1,43 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use std::sync::mpsc;
+
+use aaudio::{AAudioStreamBuilder, CallbackResult, StreamConfig, StreamProperties};
+use common::{lock, wait_until};
+
+/// Generic code written once for every type of stream.
+fn summary<S: StreamProperties>(stream: &S) -> (StreamConfig, i32) {
+    (stream.get_config(), stream.get_buffer_capacity_in_frames())
+}
+
+#[test]
+fn shared_by_every_stream_type() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_callbacks(
+            move |stream, _, _| {
+                sender.send(summary(stream)).unwrap();
+                CallbackResult::Stop
+            },
+            |_, _, _| {},
+        )
+        .open_stream()
+        .unwrap();
+    let expected = summary(&stream);
+    assert_eq!(summary(&stream.handle()), expected);
+
+    stream.request_start().unwrap();
+    wait_until(|| receiver.try_recv() == Ok(expected));
+
+    let output = AAudioStreamBuilder::new()
+        .unwrap()
+        .open_output::<f32>()
+        .unwrap();
+    assert_eq!(summary(&output), summary(&*output));
+}
@@ -0,0 +1,171 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use aaudio::mock::{self, MockDevice};
+use aaudio::{
+    AAudioStreamBuilder, AudioStreamCallback, CallbackResult, Error, ErrorKind, PanicPolicy,
+    StreamProperties, StreamState, StreamView,
+};
+use common::{lock, wait_until};
+
+#[test]
+fn restart_after_disconnect() {
+    let _lock = lock();
+    let device = mock::device();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls_clone = calls.clone();
+    let (error_sender, errors) = mpsc::channel();
+    let (route_sender, routes) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_callbacks(
+            move |_, _, _| {
+                calls_clone.fetch_add(1, Ordering::SeqCst);
+                CallbackResult::Continue
+            },
+            move |_, _, error| error_sender.send(error.kind()).unwrap(),
+        )
+        .set_auto_restart(move |stream| {
+            route_sender
+                .send((stream.get_state(), stream.get_device_id()))
+                .unwrap();
+        })
+        .open_stream()
+        .unwrap();
+    let handle = stream.handle();
+    stream.request_start().unwrap();
+    wait_until(|| calls.load(Ordering::SeqCst) > 0);
+
+    // Every restart closes the stream replaced by the previous one.
+    for device_id in 2..5 {
+        mock::set_device(MockDevice {
+            device_id,
+            ..device
+        });
+        mock::disconnect_all();
+        let (state, id) = routes.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(id, device_id);
+        assert!(state == StreamState::Starting || state == StreamState::Started);
+        assert_eq!(handle.get_device_id(), device_id);
+
+        let calls_before = calls.load(Ordering::SeqCst);
+        wait_until(|| calls.load(Ordering::SeqCst) > calls_before);
+        assert_eq!(stream.get_state(), StreamState::Started);
+    }
+    mock::set_device(device);
+    assert!(errors.try_recv().is_err());
+    stream.request_stop().unwrap();
+}
+
+#[test]
+fn restart_with_a_different_channel_count_fails() {
+    let _lock = lock();
+    let device = mock::device();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_callbacks(
+            |_, _, _| CallbackResult::Continue,
+            move |_, _, error| sender.send(error.kind()).unwrap(),
+        )
+        .set_auto_restart(|_| panic!("the stream must not be restarted"))
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+
+    mock::set_device(MockDevice {
+        channel_count: 1,
+        ..device
+    });
+    mock::disconnect_all();
+    let kind = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    mock::set_device(device);
+    assert_eq!(
+        kind,
+        ErrorKind::ChannelCountMismatch {
+            requested: device.channel_count,
+            granted: 1
+        }
+    );
+    assert_eq!(stream.get_state(), StreamState::Disconnected);
+    assert_eq!(stream.get_channel_count(), device.channel_count);
+}
+
+struct ErrorRecorder(mpsc::Sender<ErrorKind>);
+
+impl AudioStreamCallback for ErrorRecorder {
+    fn on_audio_ready(
+        &mut self,
+        _stream: &StreamView<'_>,
+        _audio_data: &mut [u8],
+        _num_frames: i32,
+    ) -> CallbackResult {
+        CallbackResult::Continue
+    }
+
+    fn on_error_before_close(&mut self, _stream: &StreamView<'_>, error: &Error) {
+        self.0.send(error.kind()).unwrap();
+    }
+}
+
+#[test]
+fn restart_error_reaches_the_callback_object() {
+    let _lock = lock();
+    let device = mock::device();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_callback(ErrorRecorder(sender))
+        .set_auto_restart(|_| panic!("the stream must not be restarted"))
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+
+    mock::set_device(MockDevice {
+        channel_count: 1,
+        ..device
+    });
+    mock::disconnect_all();
+    let kind = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    mock::set_device(device);
+    assert_eq!(
+        kind,
+        ErrorKind::ChannelCountMismatch {
+            requested: device.channel_count,
+            granted: 1
+        }
+    );
+}
+
+#[test]
+fn route_change_callback_panic_is_caught() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_callbacks(|_, _, _| CallbackResult::Continue, |_, _, _| {})
+        .set_panic_policy(PanicPolicy::Silence)
+        .set_panic_hook(move |payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string());
+            sender.send(message).unwrap();
+        })
+        .set_auto_restart(|_| panic!("route change"))
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+    mock::disconnect_all();
+
+    let message = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(message.as_deref(), Some("route change"));
+    assert_eq!(stream.get_state(), StreamState::Started);
+}
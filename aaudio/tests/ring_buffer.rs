@@ -0,0 +1,101 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use std::thread;
+
+use aaudio::{ring_buffer, AAudioStreamBuilder, Format, ResultCode, StreamProperties};
+use common::{lock, wait_until, TIMEOUT};
+
+#[test]
+fn capacity_is_a_power_of_two() {
+    let (producer, consumer) = ring_buffer::<f32>(100).unwrap();
+    assert_eq!(producer.capacity(), 128);
+    assert_eq!(consumer.capacity(), 128);
+    assert_eq!(ring_buffer::<f32>(0).unwrap().0.capacity(), 1);
+}
+
+#[test]
+fn capacity_overflow_fails() {
+    let error = ring_buffer::<f32>(usize::MAX).err().unwrap();
+    assert_eq!(error.result_code(), Some(ResultCode::IllegalArgument));
+    let error = ring_buffer::<f32>(usize::MAX / 4 + 1).err().unwrap();
+    assert_eq!(error.result_code(), Some(ResultCode::IllegalArgument));
+}
+
+#[test]
+fn push_and_pop_wrap_around() {
+    let (mut producer, mut consumer) = ring_buffer::<i16>(8).unwrap();
+    let mut out = [0i16; 8];
+    for round in 0..5i16 {
+        let samples: Vec<i16> = (0..5).map(|i| round * 10 + i).collect();
+        assert_eq!(producer.push(&samples), 5);
+        assert_eq!(consumer.len(), 5);
+        assert_eq!(producer.free_len(), 3);
+        assert_eq!(consumer.pop(&mut out), 5);
+        assert_eq!(&out[..5], &samples[..]);
+        assert!(consumer.is_empty());
+    }
+    assert_eq!(producer.get_overrun_count(), 0);
+    assert_eq!(consumer.get_underrun_count(), 0);
+}
+
+#[test]
+fn overrun_is_counted() {
+    let (mut producer, mut consumer) = ring_buffer::<i16>(4).unwrap();
+    assert_eq!(producer.push(&[1, 2, 3]), 3);
+    assert_eq!(producer.push(&[4, 5, 6]), 1);
+    assert_eq!(producer.get_overrun_count(), 1);
+    assert_eq!(producer.push_blocking(&[7], 1_000_000), 0);
+    assert_eq!(consumer.get_overrun_count(), 2);
+
+    let mut out = [0i16; 8];
+    assert_eq!(consumer.pop(&mut out), 4);
+    assert_eq!(out[..4], [1, 2, 3, 4]);
+    assert_eq!(consumer.pop(&mut out), 0);
+}
+
+#[test]
+fn push_blocking_waits_for_the_consumer() {
+    let (mut producer, mut consumer) = ring_buffer::<i32>(16).unwrap();
+    let samples: Vec<i32> = (0..1000).collect();
+    let reader = thread::spawn(move || {
+        let mut popped = Vec::new();
+        let mut out = [0i32; 7];
+        while popped.len() < 1000 {
+            let count = consumer.pop(&mut out);
+            popped.extend_from_slice(&out[..count]);
+            thread::yield_now();
+        }
+        popped
+    });
+    assert_eq!(producer.push_blocking(&samples, 5 * TIMEOUT), 1000);
+    assert_eq!(reader.join().unwrap(), samples);
+    assert_eq!(producer.get_overrun_count(), 0);
+
+    // The consumer is gone, so nothing can be pushed any more.
+    producer.push(&[0; 16]);
+    assert_eq!(producer.push_blocking(&[1], 5 * TIMEOUT), 0);
+}
+
+#[test]
+fn stream_drains_the_ring_buffer() {
+    let _lock = lock();
+    let (mut stream, mut producer) = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_channel_count(2)
+        .set_frames_per_data_callback(32)
+        .open_stream_with_ring_buffer::<i16>(1024)
+        .unwrap();
+    assert_eq!(stream.get_format(), Format::I16);
+    assert_eq!(producer.push(&[1; 256]), 256);
+    stream.request_start().unwrap();
+
+    wait_until(|| producer.free_len() == producer.capacity());
+    assert_eq!(producer.push_blocking(&[1; 4096], 5 * TIMEOUT), 4096);
+    wait_until(|| producer.get_underrun_count() > 0);
+    assert_eq!(producer.get_overrun_count(), 0);
+    stream.request_stop().unwrap();
+}
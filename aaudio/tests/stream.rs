@@ -0,0 +1,395 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+extern crate libc;
+
+mod common;
+
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use aaudio::mock::{self, MockDevice};
+use aaudio::{
+    AAudioStreamBuilder, CallbackResult, Clock, Direction, ErrorKind, Format, Mono, ResultCode,
+    Stereo, StreamProperties, StreamState, Usage,
+};
+use common::{lock, wait_until, TIMEOUT};
+
+#[test]
+fn state_transitions() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new().unwrap().open_stream().unwrap();
+    assert_eq!(stream.get_state(), StreamState::Open);
+
+    stream.request_start().unwrap();
+    let mut state = stream.get_state();
+    while state != StreamState::Started {
+        assert_eq!(state, StreamState::Starting);
+        state = stream.wait_for_state_change(state, TIMEOUT).unwrap();
+    }
+
+    stream.request_pause().unwrap();
+    let state = stream
+        .wait_for_state_change(StreamState::Pausing, TIMEOUT)
+        .unwrap();
+    assert_eq!(state, StreamState::Paused);
+
+    stream.request_flush().unwrap();
+    assert_eq!(stream.get_state(), StreamState::Flushed);
+
+    stream.request_start().unwrap();
+    stream.request_stop().unwrap();
+    let state = stream
+        .wait_for_state_change(StreamState::Stopping, TIMEOUT)
+        .unwrap();
+    assert_eq!(state, StreamState::Stopped);
+}
+
+#[test]
+fn wait_for_state_change_times_out() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new().unwrap().open_stream().unwrap();
+    let error = stream
+        .wait_for_state_change(StreamState::Open, 10_000_000)
+        .unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::Timeout));
+}
+
+#[test]
+fn unspecified_parameters_come_from_the_device() {
+    let _lock = lock();
+    let device = mock::device();
+    let stream = AAudioStreamBuilder::new().unwrap().open_stream().unwrap();
+    assert_eq!(stream.get_device_id(), device.device_id);
+    assert_eq!(stream.get_sample_rate(), device.sample_rate);
+    assert_eq!(stream.get_channel_count(), device.channel_count);
+    assert_eq!(stream.get_frames_per_burst(), device.frames_per_burst);
+
+    mock::set_device(MockDevice {
+        sample_rate: 44100,
+        ..device
+    });
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_channel_count(1)
+        .open_stream()
+        .unwrap();
+    mock::set_device(device);
+    assert_eq!(stream.get_sample_rate(), 44100);
+    assert_eq!(stream.get_channel_count(), 1);
+}
+
+#[test]
+fn blocking_write() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_format(Format::I16)
+        .set_channel_count(2)
+        .open_stream()
+        .unwrap();
+    let capacity = stream.get_buffer_capacity_in_frames();
+    let buffer = vec![0u8; capacity as usize * 4 * 2];
+
+    // The buffer is filled without blocking before the stream is started.
+    let written = stream.write(&buffer, capacity, 0).unwrap();
+    assert_eq!(written, capacity as u32);
+    assert_eq!(stream.write(&buffer, capacity, 0).unwrap(), 0);
+
+    // Once started, the device drains the buffer and the write completes.
+    stream.request_start().unwrap();
+    let written = stream.write(&buffer, capacity * 2, TIMEOUT).unwrap();
+    assert_eq!(written, capacity as u32 * 2);
+    assert_eq!(stream.get_frames_written(), capacity as i64 * 3);
+}
+
+#[test]
+fn blocking_read() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_direction(Direction::Input)
+        .set_format(Format::I16)
+        .set_channel_count(1)
+        .open_stream()
+        .unwrap();
+    let mut buffer = vec![0xFFu8; 480 * 2];
+
+    // Nothing is recorded before the stream is started.
+    assert_eq!(stream.read(&mut buffer, 480, 10_000_000).unwrap(), 0);
+
+    stream.request_start().unwrap();
+    assert_eq!(stream.read(&mut buffer, 480, TIMEOUT).unwrap(), 480);
+    assert!(buffer.iter().all(|&byte| byte == 0));
+    assert_eq!(stream.get_frames_read(), 480);
+}
+
+#[test]
+fn read_from_output_stream_fails() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new().unwrap().open_stream().unwrap();
+    let mut buffer = vec![0u8; 4096];
+    let error = stream.read(&mut buffer, 16, 0).unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::Unimplemented));
+    assert_eq!(error.operation(), "read");
+}
+
+#[test]
+fn data_callback() {
+    let _lock = lock();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let frames = Arc::new(AtomicI32::new(0));
+    let (calls_clone, frames_clone) = (calls.clone(), frames.clone());
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_format(Format::F32)
+        .set_channel_count(2)
+        .set_frames_per_data_callback(64)
+        .set_callbacks(
+            move |_, data, num_frames| {
+                assert_eq!(data.len(), num_frames as usize * 8);
+                frames_clone.store(num_frames, Ordering::SeqCst);
+                if calls_clone.fetch_add(1, Ordering::SeqCst) == 2 {
+                    CallbackResult::Stop
+                } else {
+                    CallbackResult::Continue
+                }
+            },
+            |_, _, _| {},
+        )
+        .open_stream()
+        .unwrap();
+
+    // Data is transferred by the callback.
+    let buffer = vec![0u8; 64 * 8];
+    let error = stream.write(&buffer, 64, 0).unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::InvalidState));
+
+    stream.request_start().unwrap();
+    wait_until(|| stream.get_state() == StreamState::Stopped);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert_eq!(frames.load(Ordering::SeqCst), 64);
+    assert_eq!(stream.get_frames_written(), 64 * 3);
+}
+
+#[test]
+fn disconnect_calls_error_callback() {
+    let _lock = lock();
+    let (sender, receiver) = mpsc::channel();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_callbacks(
+            |_, _, _| CallbackResult::Continue,
+            move |stream, _, error| {
+                sender.send((stream.get_state(), error)).unwrap();
+            },
+        )
+        .open_stream()
+        .unwrap();
+    stream.request_start().unwrap();
+    mock::disconnect_all();
+
+    let (state, error) = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(state, StreamState::Disconnected);
+    assert_eq!(error.kind(), ErrorKind::AAudio(ResultCode::Disconnected));
+    assert_eq!(stream.get_state(), StreamState::Disconnected);
+    let error = stream.request_start().unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::Disconnected));
+}
+
+#[test]
+fn write_checks_the_buffer_length() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_format(Format::I16)
+        .set_channel_count(2)
+        .open_stream()
+        .unwrap();
+    let buffer = vec![0u8; 16 * 4];
+    let error = stream.write(&buffer, 17, 0).unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::IllegalArgument));
+    let error = stream.write(&buffer, -1, 0).unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::IllegalArgument));
+    assert_eq!(stream.write(&buffer, 16, 0).unwrap(), 16);
+    assert_eq!(stream.get_frames_written(), 16);
+}
+
+#[test]
+fn io_without_a_frame_size_fails() {
+    let _lock = lock();
+    let device = mock::device();
+    mock::set_device(MockDevice {
+        channel_count: 0,
+        ..device
+    });
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_format(Format::I16)
+        .open_stream();
+    let (sender, receiver) = mpsc::channel();
+    let callback_stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_format(Format::I16)
+        .set_callbacks(
+            |_, _, _| panic!("the callback must not be called"),
+            move |_, _, error| sender.send(error.kind()).unwrap(),
+        )
+        .open_stream();
+    mock::set_device(device);
+
+    let mut stream = stream.unwrap();
+    assert_eq!(stream.get_channel_count(), 0);
+    let mut buffer = vec![0u8; 64];
+    let error = stream.write(&buffer, 4, 0).unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::InvalidFormat));
+    let error = stream.read(&mut buffer, 4, 0).unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::InvalidFormat));
+
+    let mut stream = callback_stream.unwrap();
+    stream.request_start().unwrap();
+    let kind = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(kind, ErrorKind::AAudio(ResultCode::InvalidFormat));
+    wait_until(|| stream.get_state() == StreamState::Stopped);
+}
+
+#[test]
+fn write_frames_checks_the_frame_type() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_format(Format::I16)
+        .set_channel_count(2)
+        .open_stream()
+        .unwrap();
+
+    let error = stream.write_frames(&[0.0f32; 8], 0).unwrap_err();
+    assert_eq!(
+        error.kind(),
+        ErrorKind::FormatMismatch {
+            requested: Format::F32,
+            granted: Format::I16,
+        }
+    );
+    let error = stream.write_frames(&[Mono(0i16); 8], 0).unwrap_err();
+    assert_eq!(
+        error.kind(),
+        ErrorKind::ChannelCountMismatch {
+            requested: 1,
+            granted: 2,
+        }
+    );
+    let error = stream.write_frames(&[0i16; 7], 0).unwrap_err();
+    assert_eq!(
+        error.kind(),
+        ErrorKind::BufferLength {
+            length: 7,
+            channel_count: 2,
+        }
+    );
+    assert_eq!(error.operation(), "write_frames");
+
+    // Interleaved samples and frames transfer the same number of frames.
+    assert_eq!(stream.write_frames(&[0i16; 8], 0).unwrap(), 4);
+    assert_eq!(
+        stream
+            .write_frames(&[Stereo::<i16>::default(); 4], 0)
+            .unwrap(),
+        4
+    );
+    assert_eq!(stream.get_frames_written(), 8);
+}
+
+#[test]
+fn read_frames() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_direction(Direction::Input)
+        .set_format(Format::F32)
+        .set_channel_count(2)
+        .open_stream()
+        .unwrap();
+    let mut buffer = [Stereo {
+        left: 1.0f32,
+        right: 1.0,
+    }; 240];
+    let error = stream.read_frames(&mut [Mono(0.0f32); 240], 0).unwrap_err();
+    assert_eq!(error.operation(), "read_frames");
+
+    stream.request_start().unwrap();
+    assert_eq!(stream.read_frames(&mut buffer, TIMEOUT).unwrap(), 240);
+    assert!(buffer.iter().all(|frame| *frame == Stereo::default()));
+}
+
+#[test]
+fn error_records_the_basic_config() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_usage(Usage::Game)
+        .unwrap()
+        .open_stream()
+        .unwrap();
+    let config = stream.get_config();
+    assert_eq!(config.usage, Some(Usage::Game));
+
+    let error = stream.read(&mut [0u8; 64], 1, 0).unwrap_err();
+    let recorded = error.config().unwrap();
+    assert_eq!(recorded.format, config.format);
+    assert_eq!(recorded.sample_rate, config.sample_rate);
+    assert_eq!(recorded.usage, None);
+    assert_eq!(recorded.content_spatialized, None);
+}
+
+#[test]
+fn hardware_info_reports_the_device_format() {
+    let _lock = lock();
+    let device = mock::device();
+    let stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_sample_rate(device.sample_rate / 2)
+        .set_channel_count(1)
+        .set_format(Format::I16)
+        .open_stream()
+        .unwrap();
+    let info = stream.get_hardware_info().unwrap();
+    assert_eq!(info.sample_rate, device.sample_rate);
+    assert_eq!(info.channel_count, device.channel_count);
+    assert_eq!(info.format, Format::F32);
+    assert_eq!(stream.get_sample_rate(), device.sample_rate / 2);
+}
+
+#[test]
+fn timestamps() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new().unwrap().open_stream().unwrap();
+    let error = stream.get_timestamp_monotonic().unwrap_err();
+    assert_eq!(error.result_code(), Some(ResultCode::InvalidState));
+
+    stream.request_start().unwrap();
+    stream.write(&vec![0u8; 480 * 8], 480, TIMEOUT).unwrap();
+    wait_until(|| stream.get_timestamp(Clock::Monotonic).is_ok());
+    for &(clock, clock_id) in &[
+        (Clock::Monotonic, libc::CLOCK_MONOTONIC),
+        (Clock::Boottime, libc::CLOCK_BOOTTIME),
+    ] {
+        let timestamp = stream.get_timestamp(clock).unwrap();
+        assert!(timestamp.frame_position > 0);
+        assert!(timestamp.time() <= now(clock_id));
+        assert_eq!(Duration::from(timestamp), timestamp.time());
+    }
+    let monotonic = stream.get_timestamp_monotonic().unwrap();
+    assert!(monotonic.time() <= now(libc::CLOCK_MONOTONIC));
+}
+
+fn now(clock_id: libc::clockid_t) -> Duration {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    assert_eq!(unsafe { libc::clock_gettime(clock_id, &mut now) }, 0);
+    Duration::new(now.tv_sec as u64, now.tv_nsec as u32)
+}
@@ -0,0 +1,109 @@
+//! A stand-in for `libaaudio` that only implements some functions of API level 26,
+//! loaded by the `dynamic` tests.
+//!
+//! Streams are opened with the sample rate of the builder and fixed values of the other
+//! parameters. They cannot be started.
+
+#![crate_type = "cdylib"]
+
+use std::os::raw::c_void;
+
+const OK: i32 = 0;
+const ERROR_INVALID_STATE: i32 = -895;
+const ERROR_NULL: i32 = -886;
+
+const DIRECTION_OUTPUT: i32 = 0;
+const FORMAT_PCM_I16: i32 = 1;
+const SHARING_MODE_SHARED: i32 = 1;
+const PERFORMANCE_MODE_NONE: i32 = 10;
+const STREAM_STATE_OPEN: i32 = 2;
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudio_createStreamBuilder(builder: *mut *mut c_void) -> i32 {
+    if builder.is_null() {
+        return ERROR_NULL;
+    }
+    *builder = Box::into_raw(Box::new(0i32)) as *mut c_void;
+    OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStreamBuilder_setSampleRate(builder: *mut c_void, sample_rate: i32) {
+    *(builder as *mut i32) = sample_rate;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStreamBuilder_openStream(
+    builder: *mut c_void,
+    stream: *mut *mut c_void,
+) -> i32 {
+    *stream = Box::into_raw(Box::new(*(builder as *mut i32))) as *mut c_void;
+    OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStreamBuilder_delete(builder: *mut c_void) -> i32 {
+    drop(Box::from_raw(builder as *mut i32));
+    OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStream_close(stream: *mut c_void) -> i32 {
+    drop(Box::from_raw(stream as *mut i32));
+    OK
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStream_requestStart(_stream: *mut c_void) -> i32 {
+    ERROR_INVALID_STATE
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStream_getState(_stream: *mut c_void) -> i32 {
+    STREAM_STATE_OPEN
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStream_getSampleRate(stream: *mut c_void) -> i32 {
+    *(stream as *mut i32)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStream_getChannelCount(_stream: *mut c_void) -> i32 {
+    2
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStream_getFormat(_stream: *mut c_void) -> i32 {
+    FORMAT_PCM_I16
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStream_getDirection(_stream: *mut c_void) -> i32 {
+    DIRECTION_OUTPUT
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStream_getSharingMode(_stream: *mut c_void) -> i32 {
+    SHARING_MODE_SHARED
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStream_getPerformanceMode(_stream: *mut c_void) -> i32 {
+    PERFORMANCE_MODE_NONE
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStream_getDeviceId(_stream: *mut c_void) -> i32 {
+    1
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStream_getFramesPerBurst(_stream: *mut c_void) -> i32 {
+    192
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn AAudioStream_getBufferCapacityInFrames(_stream: *mut c_void) -> i32 {
+    1920
+}
@@ -0,0 +1,49 @@
+#![cfg(feature = "mock")]
+
+extern crate aaudio;
+
+mod common;
+
+use aaudio::{AAudioStreamBuilder, Direction, Stereo, StreamProperties, StreamState};
+use common::{lock, TIMEOUT};
+
+#[test]
+fn output_stream() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .set_direction(Direction::Input)
+        .open_output::<Stereo<f32>>()
+        .unwrap();
+    assert_eq!(stream.get_direction(), Direction::Output);
+
+    let capacity = stream.get_buffer_capacity_in_frames() as usize;
+    let buffer = vec![Stereo::default(); capacity];
+    assert_eq!(stream.write(&buffer, 0).unwrap(), capacity as u32);
+    assert_eq!(stream.get_frames_queued(), capacity as i64);
+
+    stream.request_start().unwrap();
+    assert_eq!(stream.write(&buffer, TIMEOUT).unwrap(), capacity as u32);
+    stream.request_stop().unwrap();
+    let state = stream
+        .wait_for_state_change(StreamState::Stopping, TIMEOUT)
+        .unwrap();
+    assert_eq!(state, StreamState::Stopped);
+}
+
+#[test]
+fn input_stream() {
+    let _lock = lock();
+    let mut stream = AAudioStreamBuilder::new()
+        .unwrap()
+        .open_input::<i16>()
+        .unwrap();
+    assert_eq!(stream.get_direction(), Direction::Input);
+    assert_eq!(stream.get_frames_available(), 0);
+
+    let channel_count = stream.get_channel_count() as usize;
+    let mut buffer = vec![1i16; 480 * channel_count];
+    stream.request_start().unwrap();
+    assert_eq!(stream.read(&mut buffer, TIMEOUT).unwrap(), 480);
+    assert!(buffer.iter().all(|&sample| sample == 0));
+}